@@ -0,0 +1,24 @@
+use aoc2023::prepare_day;
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+
+const SAMPLES: [(u32, &str); 4] = [
+    (12, include_str!("../day12.test")),
+    (14, include_str!("../day14.test")),
+    (16, include_str!("../day16.test")),
+    (17, include_str!("../day17.test")),
+];
+
+fn bench_solvers(c: &mut Criterion) {
+    for (day, data) in SAMPLES {
+        c.bench_function(&format!("day{:02}", day), |b| {
+            b.iter_batched(
+                || prepare_day(day, data.to_string()).unwrap(),
+                |prepared| prepared.solve(),
+                BatchSize::SmallInput,
+            )
+        });
+    }
+}
+
+criterion_group!(benches, bench_solvers);
+criterion_main!(benches);
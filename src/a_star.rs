@@ -1,6 +1,10 @@
 #![allow(unused)]
 use priority_queue::PriorityQueue;
-use std::{collections::HashSet, fmt::Debug, hash::Hash};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Debug,
+    hash::Hash,
+};
 
 pub trait State: Sized + Eq + PartialEq + Hash {
     fn heuristic(&self) -> u64;
@@ -23,55 +27,70 @@ impl Ord for Priority {
     }
 }
 
-struct Entry<S: State> {
-    cost: u64,
-    state: S,
-    route: Vec<S>,
+pub struct Solution<S> {
+    pub cost: u64,
+    pub route: Vec<S>,
 }
 
-impl<S: State> Entry<S> {
-    fn priority(&self) -> Priority {
-        Priority(self.cost + self.state.heuristic())
+fn reconstruct_route<S: State + Clone>(came_from: &HashMap<S, S>, end: S) -> Vec<S> {
+    let mut route = vec![end];
+    while let Some(previous) = came_from.get(route.last().unwrap()) {
+        route.push(previous.clone());
     }
+    route.reverse();
+    route
 }
 
-impl<S: State> PartialEq for Entry<S> {
-    fn eq(&self, other: &Self) -> bool {
-        self.state == other.state
-    }
+pub fn solve<S: State + Clone + Debug>(
+    starts: impl Iterator<Item = S>,
+) -> Result<Solution<S>, HashSet<S>> {
+    search(starts, S::heuristic, None)
 }
 
-impl<S: State> Eq for Entry<S> {}
-
-impl<S: State> Hash for Entry<S> {
-    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
-        self.state.hash(state)
-    }
+// Give up once every remaining route is estimated to cost more than `max_cost`.
+pub fn solve_bounded<S: State + Clone + Debug>(
+    starts: impl Iterator<Item = S>,
+    max_cost: u64,
+) -> Option<Solution<S>> {
+    search(starts, S::heuristic, Some(max_cost)).ok()
 }
 
-pub struct Solution<S> {
-    pub cost: u64,
-    pub route: Vec<S>,
+// Expand states purely by the cost to reach them, ignoring the heuristic.
+pub fn dijkstra<S: State + Clone + Debug>(
+    starts: impl Iterator<Item = S>,
+) -> Result<Solution<S>, HashSet<S>> {
+    search(starts, |_| 0, None)
 }
 
-pub fn solve<S: State + Clone + Debug>(
+fn search<S: State + Clone + Debug>(
     starts: impl Iterator<Item = S>,
+    heuristic: impl Fn(&S) -> u64,
+    max_cost: Option<u64>,
 ) -> Result<Solution<S>, HashSet<S>> {
     let mut queue = PriorityQueue::new();
+    let mut costs = HashMap::new();
+    let mut came_from = HashMap::new();
+
     for start in starts {
-        let entry = Entry {
-            cost: 0,
-            state: start.clone(),
-            route: vec![start],
-        };
-        let priority = entry.priority();
-        queue.push(entry, priority);
+        let priority = Priority(heuristic(&start));
+        costs.insert(start.clone(), 0);
+        queue.push(start, priority);
     }
 
     let mut visited = HashSet::new();
 
-    while let Some((Entry { cost, state, route }, _)) = queue.pop() {
+    while let Some((state, Priority(estimate))) = queue.pop() {
+        if max_cost
+            .map(|max_cost| estimate > max_cost)
+            .unwrap_or_default()
+        {
+            break;
+        }
+
+        let cost = costs[&state];
+
         if state.is_end() {
+            let route = reconstruct_route(&came_from, state);
             return Ok(Solution { cost, route });
         }
 
@@ -82,16 +101,19 @@ pub fn solve<S: State + Clone + Debug>(
                 continue;
             }
 
-            let mut route = route.clone();
-            route.push(next_state.clone());
-            let next_entry = Entry {
-                cost: cost + delta,
-                state: next_state,
-                route,
-            };
-            let priority = next_entry.priority();
+            let next_cost = cost + delta;
+            if costs
+                .get(&next_state)
+                .map(|&prev_cost| next_cost >= prev_cost)
+                .unwrap_or_default()
+            {
+                continue;
+            }
 
-            queue.push_increase(next_entry, priority);
+            let priority = Priority(next_cost + heuristic(&next_state));
+            costs.insert(next_state.clone(), next_cost);
+            came_from.insert(next_state.clone(), state.clone());
+            queue.push(next_state, priority);
         }
     }
 
@@ -1,9 +1,12 @@
 #![allow(unused)]
 
+use failure::{err_msg, Error};
 use itertools::iproduct;
 use std::{
+    collections::HashMap,
     fmt::Display,
-    ops::{Add, Div, Mul, Sub},
+    hash::Hash,
+    ops::{Add, AddAssign, Div, Mul, Sub, SubAssign},
 };
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
@@ -20,27 +23,26 @@ impl Position {
     pub fn adjacent(self) -> impl Iterator<Item = Position> {
         [(1, 0), (0, 1), (-1, 0), (0, -1)]
             .into_iter()
-            .map(move |(dx, dy)| Position {
-                x: self.x + dx,
-                y: self.y + dy,
-            })
+            .map(move |offset: (i64, i64)| self + offset.into())
     }
 
     pub fn surrounding(&self) -> impl Iterator<Item = Position> + '_ {
-        iproduct!([-1, 0, 1], [-1, 0, 1]).filter_map(|(dx, dy)| {
+        iproduct!([-1, 0, 1], [-1, 0, 1]).filter_map(|(dx, dy): (i64, i64)| {
             if dx != 0 || dy != 0 {
-                Some(Position {
-                    x: self.x + dx,
-                    y: self.y + dy,
-                })
+                Some(*self + (dx, dy).into())
             } else {
                 None
             }
         })
     }
 
+    pub fn delta(&self, other: &Self) -> Position {
+        *other - *self
+    }
+
     pub fn direction_to(&self, other: &Self) -> Option<Direction> {
-        match (other.x - self.x, other.y - self.y) {
+        let delta = self.delta(other);
+        match (delta.x, delta.y) {
             (0, dy) if dy < 0 => Some(Direction::North),
             (dx, 0) if dx > 0 => Some(Direction::East),
             (0, dy) if dy > 0 => Some(Direction::South),
@@ -54,7 +56,7 @@ impl Position {
     }
 
     pub fn points_to(self, other: Position) -> impl Iterator<Item = Position> {
-        let diff = other - self;
+        let diff = self.delta(&other);
         assert!(diff.x == 0 || diff.y == 0);
         let distance = diff.length();
         let delta = diff / distance;
@@ -69,6 +71,18 @@ impl Position {
         self + direction.offset() * len as i64
     }
 
+    // Reduce the position into a grid of the given size which tiles the plane.
+    pub fn wrap(self, width: i64, height: i64) -> Self {
+        Position {
+            x: self.x.rem_euclid(width),
+            y: self.y.rem_euclid(height),
+        }
+    }
+
+    pub fn wrapping_step(self, direction: Direction, width: i64, height: i64) -> Self {
+        self.step(direction).wrap(width, height)
+    }
+
     pub fn origin() -> Self {
         Position { x: 0, y: 0 }
     }
@@ -99,6 +113,13 @@ impl Add for Position {
     }
 }
 
+impl AddAssign for Position {
+    fn add_assign(&mut self, rhs: Self) {
+        self.x += rhs.x;
+        self.y += rhs.y;
+    }
+}
+
 impl Sub for Position {
     type Output = Self;
     fn sub(self, rhs: Self) -> Self::Output {
@@ -109,6 +130,13 @@ impl Sub for Position {
     }
 }
 
+impl SubAssign for Position {
+    fn sub_assign(&mut self, rhs: Self) {
+        self.x -= rhs.x;
+        self.y -= rhs.y;
+    }
+}
+
 impl Div<i64> for Position {
     type Output = Self;
     fn div(self, rhs: i64) -> Self::Output {
@@ -169,33 +197,269 @@ impl Direction {
         }
     }
 
+    // Turn clockwise by the given number of quarter turns, or anticlockwise
+    // if negative.
+    pub fn rotate(self, quarter_turns: i32) -> Direction {
+        use Direction::*;
+        let directions = [North, East, South, West];
+        directions[(self as i32 + quarter_turns).rem_euclid(4) as usize]
+    }
+
     pub fn reverse(self) -> Direction {
+        self.rotate(2)
+    }
+
+    pub fn turn_left(self) -> Direction {
+        self.rotate(-1)
+    }
+
+    pub fn turn_right(self) -> Direction {
+        self.rotate(1)
+    }
+}
+
+// Accepts either up/down/left/right or compass letters.
+impl TryFrom<char> for Direction {
+    type Error = Error;
+
+    fn try_from(value: char) -> Result<Self, Self::Error> {
         use Direction::*;
-        match self {
-            North => South,
-            East => West,
-            South => North,
-            West => East,
+        match value {
+            'U' | 'N' => Ok(North),
+            'R' | 'E' => Ok(East),
+            'D' | 'S' => Ok(South),
+            'L' | 'W' => Ok(West),
+            _ => Err(err_msg(format!("Invalid direction: {}", value))),
         }
     }
+}
 
-    pub fn turn_left(self) -> Direction {
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Grid<T> {
+    cells: Vec<T>,
+    width: usize,
+    height: usize,
+}
+
+impl<T> Grid<T> {
+    pub fn from_char_grid(data: &str, mut parse: impl FnMut(char) -> T) -> Result<Self, Error> {
+        let mut cells = vec![];
+        let mut width = None;
+        let mut height = 0;
+
+        for line in data.lines() {
+            let len = line.chars().count();
+            if *width.get_or_insert(len) != len {
+                return Err(err_msg(format!(
+                    "Line {} has inconsistent width",
+                    height + 1
+                )));
+            }
+
+            cells.extend(line.chars().map(&mut parse));
+            height += 1;
+        }
+
+        Ok(Grid {
+            cells,
+            width: width.unwrap_or_default(),
+            height,
+        })
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn in_bounds(&self, position: Position) -> bool {
+        position.x >= 0
+            && position.x < self.width as i64
+            && position.y >= 0
+            && position.y < self.height as i64
+    }
+
+    fn index(&self, position: Position) -> Option<usize> {
+        if self.in_bounds(position) {
+            Some(position.y as usize * self.width + position.x as usize)
+        } else {
+            None
+        }
+    }
+
+    pub fn get(&self, position: Position) -> Option<&T> {
+        self.index(position).map(|index| &self.cells[index])
+    }
+
+    pub fn get_mut(&mut self, position: Position) -> Option<&mut T> {
+        self.index(position).map(|index| &mut self.cells[index])
+    }
+
+    pub fn row(&self, y: usize) -> &[T] {
+        &self.cells[y * self.width..(y + 1) * self.width]
+    }
+
+    pub fn column(&self, x: usize) -> impl Iterator<Item = &T> {
+        self.cells.iter().skip(x).step_by(self.width)
+    }
+
+    pub fn iter_positions(&self) -> impl Iterator<Item = Position> {
+        iproduct!(0..self.height, 0..self.width).map(|(y, x)| (x, y).into())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Position, &T)> {
+        self.iter_positions().zip(self.cells.iter())
+    }
+}
+
+// Apply `step` to `initial` `total` times, skipping ahead once the states
+// start repeating.
+pub fn find_cycle_and_project<S, F>(initial: S, mut step: F, total: usize) -> S
+where
+    S: Hash + Eq + Clone,
+    F: FnMut(&S) -> S,
+{
+    let mut visited = HashMap::new();
+    let mut state = initial;
+
+    for index in 0..total {
+        if let Some(prev_index) = visited.insert(state.clone(), index) {
+            let cycle_len = index - prev_index;
+            for _ in 0..(total - index) % cycle_len {
+                state = step(&state);
+            }
+            return state;
+        }
+
+        state = step(&state);
+    }
+
+    state
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn grid_access() {
+        let mut grid = Grid::from_char_grid("abc\ndef\n", |c| c).unwrap();
+        assert_eq!((grid.width(), grid.height()), (3, 2));
+        assert_eq!(grid.get(Position { x: 2, y: 1 }), Some(&'f'));
+        assert_eq!(grid.get(Position { x: 3, y: 0 }), None);
+        assert_eq!(grid.get(Position { x: 0, y: -1 }), None);
+
+        *grid.get_mut(Position { x: 1, y: 0 }).unwrap() = 'z';
+        assert_eq!(grid.row(0), &['a', 'z', 'c']);
+        assert!(grid.get_mut(Position { x: 0, y: 2 }).is_none());
+
+        assert!(grid.in_bounds(Position { x: 0, y: 0 }));
+        assert!(grid.in_bounds(Position { x: 2, y: 1 }));
+        assert!(!grid.in_bounds(Position { x: -1, y: 0 }));
+        assert!(!grid.in_bounds(Position { x: 0, y: 2 }));
+
+        let positions: Vec<Position> = grid.iter_positions().collect();
+        assert_eq!(positions.len(), 6);
+        assert_eq!(positions[1], Position { x: 1, y: 0 });
+        assert_eq!(positions[3], Position { x: 0, y: 1 });
+        assert_eq!(grid.column(1).collect::<String>(), "ze");
+
+        assert!(Grid::from_char_grid("ab\nc\n", |c| c).is_err());
+    }
+
+    #[test]
+    fn project_through_cycle() {
+        // 0 -> 1 -> 2 -> 3 -> 4 -> 5 -> 2 -> ..., so two states before a cycle of four.
+        let step = |state: &u32| if *state == 5 { 2 } else { state + 1 };
+        let naive = |total: usize| (0..total).fold(0, |state, _| step(&state));
+
+        for total in 0..50 {
+            assert_eq!(find_cycle_and_project(0, step, total), naive(total));
+        }
+
+        let mut calls = 0;
+        let state = find_cycle_and_project(
+            0,
+            |state| {
+                calls += 1;
+                step(state)
+            },
+            1_000_000_000,
+        );
+        assert_eq!(state, 2 + (1_000_000_000 - 2) % 4);
+        assert!(calls < 20);
+    }
+
+    #[test]
+    fn direction_from_char() {
         use Direction::*;
-        match self {
-            North => West,
-            East => North,
-            South => East,
-            West => South,
+        for (c, direction) in [
+            ('U', North),
+            ('N', North),
+            ('R', East),
+            ('E', East),
+            ('D', South),
+            ('S', South),
+            ('L', West),
+            ('W', West),
+        ] {
+            assert_eq!(Direction::try_from(c).unwrap(), direction);
         }
+        assert!(Direction::try_from('X').is_err());
     }
 
-    pub fn turn_right(self) -> Direction {
+    #[test]
+    fn rotate_directions() {
         use Direction::*;
-        match self {
-            North => East,
-            East => South,
-            South => West,
-            West => North,
+        assert_eq!(North.turn_right(), East);
+        assert_eq!(North.turn_left(), West);
+        assert_eq!(West.turn_right(), North);
+
+        for direction in Direction::all() {
+            assert_eq!(direction.rotate(2), direction.reverse());
+            assert_eq!(direction.rotate(1), direction.turn_right());
+            assert_eq!(direction.rotate(-1), direction.turn_left());
+            assert_eq!(direction.rotate(4), direction);
+            assert_eq!(direction.rotate(-7), direction.rotate(1));
         }
     }
+
+    #[test]
+    fn position_arithmetic() {
+        let a = Position { x: 1, y: 2 };
+        let b = Position { x: 4, y: -3 };
+        assert_eq!(a + b, Position { x: 5, y: -1 });
+        assert_eq!(b - a, Position { x: 3, y: -5 });
+        assert_eq!(a * 3, Position { x: 3, y: 6 });
+        assert_eq!(a.delta(&b), b - a);
+
+        let mut c = a;
+        c += b;
+        assert_eq!(c, a + b);
+        c -= b;
+        assert_eq!(c, a);
+    }
+
+    #[test]
+    fn wrapping_steps() {
+        use Direction::*;
+        let corner = Position { x: 4, y: 2 };
+        assert_eq!(
+            Position::origin().wrapping_step(West, 5, 3),
+            Position { x: 4, y: 0 }
+        );
+        assert_eq!(
+            Position::origin().wrapping_step(North, 5, 3),
+            Position { x: 0, y: 2 }
+        );
+        assert_eq!(corner.wrapping_step(East, 5, 3), Position { x: 0, y: 2 });
+        assert_eq!(corner.wrapping_step(South, 5, 3), Position { x: 4, y: 0 });
+        assert_eq!(
+            Position { x: -7, y: -4 }.wrapping_step(West, 5, 3),
+            Position { x: 2, y: 2 }
+        );
+    }
 }
@@ -2,51 +2,49 @@ use failure::Error;
 
 pub struct Solver {}
 
-fn get_digits_part1(line: &str) -> Vec<u32> {
-    line.chars().filter_map(|c| c.to_digit(10)).collect()
-}
+const DIGIT_WORDS: [&str; 9] = [
+    "one", "two", "three", "four", "five", "six", "seven", "eight", "nine",
+];
 
-fn get_digits_part2(line: &str) -> Vec<u32> {
+fn get_digits(line: &str, include_words: bool) -> Vec<u32> {
     let mut digits = vec![];
-    for index in 0..line.len() {
-        let substr = &line[index..];
-        if let Some(digit) = substr.chars().next().unwrap().to_digit(10) {
+    for (index, c) in line.char_indices() {
+        if let Some(digit) = c.to_digit(10) {
             digits.push(digit);
-        } else if substr.starts_with("one") {
-            digits.push(1);
-        } else if substr.starts_with("two") {
-            digits.push(2);
-        } else if substr.starts_with("three") {
-            digits.push(3);
-        } else if substr.starts_with("four") {
-            digits.push(4);
-        } else if substr.starts_with("five") {
-            digits.push(5);
-        } else if substr.starts_with("six") {
-            digits.push(6);
-        } else if substr.starts_with("seven") {
-            digits.push(7);
-        } else if substr.starts_with("eight") {
-            digits.push(8);
-        } else if substr.starts_with("nine") {
-            digits.push(9);
+        } else if include_words {
+            let substr = &line[index..];
+            if let Some(digit) = (1..).zip(DIGIT_WORDS).find_map(|(digit, word)| {
+                if substr.starts_with(word) {
+                    Some(digit)
+                } else {
+                    None
+                }
+            }) {
+                digits.push(digit);
+            }
         }
     }
     digits
 }
 
-fn solve<F>(lines: &[String], get_digits: F) -> u32
-where
-    F: Fn(&str) -> Vec<u32>,
-{
+fn calibration_value(line: &str, include_words: bool) -> Option<u32> {
+    let digits = get_digits(line, include_words);
+    Some(digits.first()? * 10 + digits.last()?)
+}
+
+// Lines without any digits (such as blank lines) have no calibration value and
+// are skipped.
+fn solve(lines: &[String], include_words: bool) -> u32 {
     lines
         .iter()
-        .map(|line| get_digits(line))
-        .map(|digits: Vec<u32>| (*digits.first().unwrap(), *digits.last().unwrap()))
-        .map(|(x, y)| x * 10 + y)
+        .filter_map(|line| calibration_value(line, include_words))
         .sum()
 }
 
+fn solve_both(lines: &[String]) -> (u32, u32) {
+    (solve(lines, false), solve(lines, true))
+}
+
 impl super::Solver for Solver {
     type Problem = Vec<String>;
 
@@ -55,9 +53,41 @@ impl super::Solver for Solver {
     }
 
     fn solve(lines: Self::Problem) -> (Option<String>, Option<String>) {
-        let part1: u32 = solve(&lines, get_digits_part1);
-        let part2: u32 = solve(&lines, get_digits_part2);
+        let (part1, part2) = solve_both(&lines);
 
         (Some(part1.to_string()), Some(part2.to_string()))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn overlapping_words() {
+        assert_eq!(calibration_value("eightwothree", true), Some(83));
+        assert_eq!(calibration_value("eightwothree", false), None);
+        assert_eq!(calibration_value("zoneight234", true), Some(14));
+    }
+
+    fn lines(data: &str) -> Vec<String> {
+        data.lines().map(String::from).collect()
+    }
+
+    #[test]
+    fn solve_both_on_samples() {
+        let sample1 = lines("1abc2\npqr3stu8vwx\na1b2c3d4e5f\ntreb7uchet\n");
+        let sample2 = lines(
+            "two1nine
+eightwothree
+abcone2threexyz
+xtwone3four
+4nineeightseven2
+zoneight234
+7pqrstsixteen
+",
+        );
+        assert_eq!(solve_both(&sample1).0, 142);
+        assert_eq!(solve_both(&sample2).1, 281);
+    }
+}
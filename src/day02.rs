@@ -1,10 +1,9 @@
 mod parse {
     use failure::{err_msg, Error};
     use nom::{
-        branch::alt,
         bytes::complete::tag,
-        character::complete::newline,
-        combinator::{all_consuming, map, value},
+        character::complete::{alpha1, newline},
+        combinator::{all_consuming, map, map_res},
         multi::{many1, separated_list1},
         sequence::{preceded, separated_pair, terminated, tuple},
         IResult,
@@ -15,11 +14,7 @@ mod parse {
     use super::Colour;
 
     fn colour(input: &str) -> IResult<&str, Colour> {
-        alt((
-            value(Colour::Blue, tag("blue")),
-            value(Colour::Red, tag("red")),
-            value(Colour::Green, tag("green")),
-        ))(input)
+        map_res(alpha1, str::parse)(input)
     }
 
     fn amount(input: &str) -> IResult<&str, (usize, Colour)> {
@@ -54,25 +49,59 @@ mod parse {
     }
 }
 
-use failure::Error;
+use failure::{err_msg, Error};
 use parse::parse_input;
-use std::cmp::max;
+use std::{cmp::max, fmt::Display, str::FromStr};
 
-#[derive(PartialEq, Eq, Hash, Clone, Copy)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
 enum Colour {
     Red,
     Green,
     Blue,
 }
 
-fn is_round_possible(round: &[usize; 3], candidate: &[usize; 3]) -> bool {
-    round.iter().zip(candidate.iter()).all(|(x, y)| x <= y)
+// In the same order as the counts in each round.
+const COLOURS: [Colour; 3] = [Colour::Red, Colour::Green, Colour::Blue];
+
+impl Colour {
+    fn name(self) -> &'static str {
+        match self {
+            Colour::Red => "red",
+            Colour::Green => "green",
+            Colour::Blue => "blue",
+        }
+    }
+}
+
+impl Display for Colour {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for Colour {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        COLOURS
+            .into_iter()
+            .find(|colour| colour.name() == s)
+            .ok_or_else(|| err_msg(format!("Unknown colour {}", s)))
+    }
+}
+
+fn first_exceeding_round(rounds: &[[usize; 3]], candidate: &[usize; 3]) -> Option<(usize, Colour)> {
+    rounds.iter().enumerate().find_map(|(index, round)| {
+        COLOURS
+            .into_iter()
+            .zip(round.iter().zip(candidate.iter()))
+            .find(|(_, (x, y))| x > y)
+            .map(|(colour, _)| (index, colour))
+    })
 }
 
 fn is_game_possible(rounds: &[[usize; 3]], candidate: &[usize; 3]) -> bool {
-    rounds
-        .iter()
-        .all(|round| is_round_possible(round, candidate))
+    first_exceeding_round(rounds, candidate).is_none()
 }
 
 fn game_min_cubes(rounds: &[[usize; 3]]) -> [usize; 3] {
@@ -89,6 +118,34 @@ fn game_min_cubes(rounds: &[[usize; 3]]) -> [usize; 3] {
         .unwrap()
 }
 
+// Sum of the IDs of the games that could have been played with a bag
+// containing `candidate` red, green and blue cubes.
+pub fn sum_possible_game_ids(games: &[Vec<[usize; 3]>], candidate: [usize; 3]) -> usize {
+    (1..)
+        .zip(games.iter())
+        .filter_map(|(game_id, game)| {
+            if is_game_possible(game, &candidate) {
+                Some(game_id)
+            } else {
+                None
+            }
+        })
+        .sum()
+}
+
+fn solve_both(games: &[Vec<[usize; 3]>], candidate: [usize; 3]) -> (usize, usize) {
+    let part1 = sum_possible_game_ids(games, candidate);
+
+    let part2 = games
+        .iter()
+        .map(Vec::as_slice)
+        .map(game_min_cubes)
+        .map(|min_cubes| min_cubes.iter().product::<usize>())
+        .sum();
+
+    (part1, part2)
+}
+
 pub struct Solver {}
 
 impl super::Solver for Solver {
@@ -99,26 +156,61 @@ impl super::Solver for Solver {
     }
 
     fn solve(games: Self::Problem) -> (Option<String>, Option<String>) {
-        let candidate = [12, 13, 14];
-
-        let part1: usize = (1..)
-            .zip(games.iter())
-            .filter_map(|(game_id, game)| {
-                if is_game_possible(game, &candidate) {
-                    Some(game_id)
-                } else {
-                    None
-                }
-            })
-            .sum();
-
-        let part2: usize = games
-            .iter()
-            .map(Vec::as_slice)
-            .map(game_min_cubes)
-            .map(|min_cubes| min_cubes.iter().product::<usize>())
-            .sum();
-
+        let (part1, part2) = solve_both(&games, [12, 13, 14]);
         (Some(part1.to_string()), Some(part2.to_string()))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = "Game 1: 3 blue, 4 red; 1 red, 2 green, 6 blue; 2 green
+Game 2: 1 blue, 2 green; 3 green, 4 blue, 1 red; 1 green, 1 blue
+Game 3: 8 green, 6 blue, 20 red; 5 blue, 4 red, 13 green; 5 green, 1 red
+Game 4: 1 green, 3 red, 6 blue; 3 green, 6 red; 3 green, 15 blue, 14 red
+Game 5: 6 red, 1 blue, 3 green; 2 blue, 1 red, 2 green
+";
+
+    fn parse() -> Vec<Vec<[usize; 3]>> {
+        <Solver as crate::Solver>::parse_input(SAMPLE.to_string()).unwrap()
+    }
+
+    #[test]
+    fn first_exceeding_round_finds_colour() {
+        assert_eq!(
+            first_exceeding_round(&[[1, 1, 1], [2, 2, 2], [1, 1, 9]], &[5, 5, 5]),
+            Some((2, Colour::Blue))
+        );
+
+        let games = parse();
+        assert_eq!(
+            first_exceeding_round(&games[2], &[12, 13, 14]),
+            Some((0, Colour::Red))
+        );
+        assert_eq!(first_exceeding_round(&games[0], &[12, 13, 14]), None);
+    }
+
+    #[test]
+    fn candidate_changes_possible_games() {
+        let games = parse();
+        assert_eq!(sum_possible_game_ids(&games, [12, 13, 14]), 8);
+        assert_eq!(sum_possible_game_ids(&games, [20, 13, 15]), 15);
+        assert_eq!(sum_possible_game_ids(&games, [4, 3, 6]), 3);
+    }
+
+    #[test]
+    fn colour_round_trips() {
+        for colour in COLOURS {
+            assert_eq!(colour.to_string().parse::<Colour>().unwrap(), colour);
+        }
+        assert_eq!(Colour::Green.to_string(), "green");
+        assert!("purple".parse::<Colour>().is_err());
+        assert!(<Solver as crate::Solver>::parse_input("Game 1: 3 purple\n".to_string()).is_err());
+    }
+
+    #[test]
+    fn solve_both_on_sample() {
+        assert_eq!(solve_both(&parse(), [12, 13, 14]), (8, 2286));
+    }
+}
@@ -1,51 +1,73 @@
 use crate::common::Position;
 use failure::Error;
-use std::collections::{HashMap, HashSet};
+use std::{
+    collections::{HashMap, HashSet},
+    ops::Range,
+};
 
 pub struct Solver {}
 
-fn find_numbers(grid: &HashMap<Position, char>) -> Vec<(u64, HashSet<Position>)> {
+struct Number {
+    value: u64,
+    y: i64,
+    xs: Range<i64>,
+}
+
+impl Number {
+    fn positions(&self) -> impl Iterator<Item = Position> + '_ {
+        self.xs.clone().map(|x| Position { x, y: self.y })
+    }
+}
+
+fn find_numbers(grid: &HashMap<Position, char>) -> Vec<Number> {
     let max_x = grid.keys().map(|pos| pos.x).max().unwrap();
     let max_y = grid.keys().map(|pos| pos.y).max().unwrap();
 
     let mut numbers = vec![];
 
-    let mut current_number = 0;
-    let mut current_positions = HashSet::new();
-
     for y in 0..=max_y {
+        let mut current_number = 0;
+        let mut current_start = None;
+
         for x in 0..=max_x {
-            let pos = Position { x, y };
-            let c = grid.get(&pos).unwrap();
+            let c = grid.get(&Position { x, y }).unwrap();
             if let Some(digit) = c.to_digit(10) {
                 current_number = digit as u64 + current_number * 10;
-                current_positions.insert(pos);
-            } else if !current_positions.is_empty() {
-                numbers.push((current_number, current_positions));
+                current_start.get_or_insert(x);
+            } else if let Some(start) = current_start.take() {
+                numbers.push(Number {
+                    value: current_number,
+                    y,
+                    xs: start..x,
+                });
                 current_number = 0;
-                current_positions = HashSet::new();
             }
         }
 
-        if !current_positions.is_empty() {
-            numbers.push((current_number, current_positions));
-            current_number = 0;
-            current_positions = HashSet::new();
+        if let Some(start) = current_start {
+            numbers.push(Number {
+                value: current_number,
+                y,
+                xs: start..max_x + 1,
+            });
         }
     }
 
     numbers
 }
 
+// Map from the position of each digit to the index of the number it's in.
+fn number_index(numbers: &[Number]) -> HashMap<Position, usize> {
+    numbers
+        .iter()
+        .enumerate()
+        .flat_map(|(id, number)| number.positions().map(move |pos| (pos, id)))
+        .collect()
+}
+
 fn find_positions_near_symbols(grid: &HashMap<Position, char>) -> HashSet<Position> {
     grid.iter()
-        .filter_map(|(pos, c)| {
-            if !c.is_ascii_digit() && *c != '.' {
-                Some(pos)
-            } else {
-                None
-            }
-        })
+        .filter_map(|(pos, c)| if is_symbol(*c) { Some(pos) } else { None })
         .flat_map(|pos| pos.surrounding())
         .collect()
 }
@@ -54,42 +76,68 @@ fn is_part_number(pos: &Position, near_symbols: &HashSet<Position>) -> bool {
     near_symbols.contains(pos)
 }
 
-fn find_part_numbers(grid: &HashMap<Position, char>) -> Vec<u64> {
+fn part_numbers(grid: &HashMap<Position, char>) -> Vec<Number> {
     let near_symbols = find_positions_near_symbols(grid);
     find_numbers(grid)
         .into_iter()
-        .filter_map(|(num, positions)| {
-            if positions
+        .filter(|number| {
+            number
+                .positions()
+                .any(|pos| is_part_number(&pos, &near_symbols))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+fn part_numbers_with_positions(grid: &HashMap<Position, char>) -> Vec<(u64, HashSet<Position>)> {
+    part_numbers(grid)
+        .into_iter()
+        .map(|number| (number.value, number.positions().collect()))
+        .collect()
+}
+
+fn find_part_numbers(grid: &HashMap<Position, char>) -> Vec<u64> {
+    part_numbers(grid)
+        .into_iter()
+        .map(|number| number.value)
+        .collect()
+}
+
+fn is_symbol(c: char) -> bool {
+    !c.is_ascii_digit() && c != '.'
+}
+
+#[cfg(test)]
+fn symbol_adjacency_count(grid: &HashMap<Position, char>) -> Vec<(u64, usize)> {
+    find_numbers(grid)
+        .into_iter()
+        .map(|number| {
+            let positions: Vec<Position> = number.positions().collect();
+            let symbols: HashSet<Position> = positions
                 .iter()
-                .any(|pos| is_part_number(pos, &near_symbols))
-            {
-                Some(num)
-            } else {
-                None
-            }
+                .flat_map(|pos| pos.surrounding())
+                .filter(|pos| grid.get(pos).map(|c| is_symbol(*c)).unwrap_or_default())
+                .collect();
+            (number.value, symbols.len())
         })
         .collect()
 }
 
-fn find_gear_ratios(grid: &HashMap<Position, char>) -> Vec<u64> {
+// Product of the numbers around each `symbol` that is next to exactly
+// `n` numbers.
+fn symbol_ratios(grid: &HashMap<Position, char>, symbol: char, n: usize) -> Vec<u64> {
     let numbers = find_numbers(grid);
+    let index = number_index(&numbers);
     grid.iter()
-        .filter_map(|(pos, c)| if *c == '*' { Some(pos) } else { None })
+        .filter_map(|(pos, c)| if *c == symbol { Some(pos) } else { None })
         .map(|pos| {
-            numbers
-                .iter()
-                .filter_map(|(num, positions)| {
-                    if pos.surrounding().any(|p| positions.contains(&p)) {
-                        Some(*num)
-                    } else {
-                        None
-                    }
-                })
+            pos.surrounding()
+                .filter_map(|p| index.get(&p).copied())
                 .collect()
         })
-        .filter_map(|numbers: Vec<u64>| {
-            if numbers.len() == 2 {
-                Some(numbers.iter().product())
+        .filter_map(|ids: HashSet<usize>| {
+            if ids.len() == n {
+                Some(ids.iter().map(|id| numbers[*id].value).product())
             } else {
                 None
             }
@@ -97,6 +145,10 @@ fn find_gear_ratios(grid: &HashMap<Position, char>) -> Vec<u64> {
         .collect()
 }
 
+fn find_gear_ratios(grid: &HashMap<Position, char>) -> Vec<u64> {
+    symbol_ratios(grid, '*', 2)
+}
+
 impl super::Solver for Solver {
     type Problem = HashMap<Position, char>;
 
@@ -124,3 +176,113 @@ impl super::Solver for Solver {
         (Some(part_one.to_string()), Some(part_two.to_string()))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = "467..114..
+...*......
+..35..633.
+......#...
+617*......
+.....+.58.
+..592.....
+......755.
+...$.*....
+.664.598..
+";
+
+    fn parse(data: &str) -> HashMap<Position, char> {
+        <Solver as crate::Solver>::parse_input(data.to_string()).unwrap()
+    }
+
+    #[test]
+    fn number_between_two_symbols() {
+        assert_eq!(
+            symbol_adjacency_count(&parse("*12#\n....\n")),
+            vec![(12, 2)]
+        );
+        assert_eq!(
+            symbol_adjacency_count(&parse("..12.\n.....\n")),
+            vec![(12, 0)]
+        );
+    }
+
+    #[test]
+    fn solve_on_sample() {
+        assert_eq!(
+            <Solver as crate::Solver>::solve(parse(SAMPLE)),
+            (Some("4361".to_string()), Some("467835".to_string()))
+        );
+    }
+
+    #[test]
+    fn ratios_around_other_symbol() {
+        let mut ratios = symbol_ratios(&parse(&SAMPLE.replace('*', "%")), '%', 2);
+        ratios.sort();
+        assert_eq!(ratios, vec![16345, 451490]);
+        assert!(symbol_ratios(&parse(&SAMPLE.replace('*', "%")), '*', 2).is_empty());
+    }
+
+    #[test]
+    fn part_numbers_with_positions_on_sample() {
+        let parts = part_numbers_with_positions(&parse(SAMPLE));
+        assert_eq!(parts.len(), 8);
+        let (_, positions) = parts.iter().find(|(value, _)| *value == 633).unwrap();
+        assert_eq!(
+            *positions,
+            [(6, 2), (7, 2), (8, 2)]
+                .into_iter()
+                .map(|(x, y)| Position { x, y })
+                .collect()
+        );
+    }
+
+    fn brute_force_gear_ratio_sum(grid: &HashMap<Position, char>) -> u64 {
+        let numbers = part_numbers_with_positions(grid);
+        grid.iter()
+            .filter(|(_, c)| **c == '*')
+            .map(|(pos, _)| {
+                let adjacent: Vec<u64> = numbers
+                    .iter()
+                    .filter(|(_, positions)| pos.surrounding().any(|p| positions.contains(&p)))
+                    .map(|(value, _)| *value)
+                    .collect();
+                if adjacent.len() == 2 {
+                    adjacent[0] * adjacent[1]
+                } else {
+                    0
+                }
+            })
+            .sum()
+    }
+
+    #[test]
+    fn gear_ratios_on_dense_grid() {
+        let mut seed = 12345u64;
+        let mut data = String::new();
+        for _ in 0..60 {
+            for _ in 0..60 {
+                seed = seed
+                    .wrapping_mul(6364136223846793005)
+                    .wrapping_add(1442695040888963407);
+                data.push(match (seed >> 33) % 10 {
+                    0..=3 => '.',
+                    4 => '*',
+                    5 => '#',
+                    _ => char::from(b'0' + ((seed >> 40) % 10) as u8),
+                });
+            }
+            data.push('\n');
+        }
+        let grid = parse(&data);
+
+        assert!(find_numbers(&grid).len() > 300);
+        assert_eq!(
+            find_gear_ratios(&grid).iter().sum::<u64>(),
+            brute_force_gear_ratio_sum(&grid)
+        );
+        assert_eq!(find_gear_ratios(&parse(SAMPLE)).iter().sum::<u64>(), 467835);
+    }
+}
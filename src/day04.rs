@@ -1,17 +1,14 @@
 use std::collections::HashSet;
 
-use crate::parsers::unsigned;
+use crate::parsers::{int_list, unsigned};
 use failure::{err_msg, Error};
 use nom::bytes::complete::tag;
-use nom::character::complete::{newline, space0, space1};
+use nom::character::complete::{newline, space1};
 use nom::combinator::{all_consuming, map};
-use nom::multi::{many1, separated_list1};
+use nom::multi::many1;
 use nom::sequence::{preceded, separated_pair, terminated, tuple};
-use nom::IResult;
-
-fn numbers(input: &str) -> IResult<&str, Vec<u64>> {
-    preceded(space0, separated_list1(space1, unsigned))(input)
-}
+#[cfg(test)]
+use num::BigUint;
 
 pub struct Card {
     winning_numbers: Vec<u64>,
@@ -28,6 +25,18 @@ impl Card {
         }
     }
 
+    // Same as `score` but without overflowing for cards with lots of
+    // winning numbers.
+    #[cfg(test)]
+    fn score_big(&self) -> BigUint {
+        let num_common = self.num_winning_numbers();
+        if num_common > 0 {
+            BigUint::from(2_u32).pow(num_common as u32 - 1)
+        } else {
+            BigUint::from(0_u32)
+        }
+    }
+
     fn num_winning_numbers(&self) -> usize {
         let winning_numbers: HashSet<_> = self.winning_numbers.iter().cloned().collect();
         let card_numbers: HashSet<_> = self.card_numbers.iter().cloned().collect();
@@ -39,7 +48,9 @@ fn copies_of_scratchcards(cards: &[Card]) -> Vec<usize> {
     let mut num_copies: Vec<usize> = cards.iter().map(|_| 1).collect();
 
     for (index, card) in cards.iter().enumerate() {
-        for offset in 1..=card.num_winning_numbers() {
+        // Don't hand out copies of cards beyond the end of the table.
+        let max_offset = card.num_winning_numbers().min(cards.len() - index - 1);
+        for offset in 1..=max_offset {
             num_copies[index + offset] += num_copies[index];
         }
     }
@@ -55,8 +66,8 @@ impl super::Solver for Solver {
     fn parse_input(data: String) -> Result<Self::Problem, Error> {
         let card = map(
             preceded(
-                tuple((tag("Card"), space1, unsigned::<u64>, tag(": "))),
-                separated_pair(numbers, tag(" | "), numbers),
+                tuple((tag("Card"), space1, unsigned::<u64>, tag(":"))),
+                separated_pair(int_list, tag("|"), int_list),
             ),
             |(winning_numbers, card_numbers)| Card {
                 winning_numbers,
@@ -75,3 +86,37 @@ impl super::Solver for Solver {
         (Some(part1.to_string()), Some(part2.to_string()))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(data: &str) -> (Option<String>, Option<String>) {
+        <Solver as crate::Solver>::solve(
+            <Solver as crate::Solver>::parse_input(data.to_string()).unwrap(),
+        )
+    }
+
+    #[test]
+    fn last_card_with_too_many_matches() {
+        let cards = "Card 1: 1 2 | 1 3\nCard 2: 1 2 3 | 1 2 3\n";
+        assert_eq!(run(cards), (Some("5".to_string()), Some("3".to_string())));
+    }
+
+    #[test]
+    fn score_big_with_many_matches() {
+        let numbers = (1..=70)
+            .map(|n| n.to_string())
+            .collect::<Vec<_>>()
+            .join(" ");
+        let cards =
+            <Solver as crate::Solver>::parse_input(format!("Card 1: {} | {}\n", numbers, numbers))
+                .unwrap();
+        assert_eq!(cards[0].score_big(), BigUint::from(2_u32).pow(69));
+        assert_eq!(cards[0].score_big().to_string(), "590295810358705651712");
+
+        let card =
+            <Solver as crate::Solver>::parse_input("Card 1: 1 2 | 1 2\n".to_string()).unwrap();
+        assert_eq!(card[0].score_big(), BigUint::from(card[0].score()));
+    }
+}
@@ -40,6 +40,7 @@ mod parse {
 
 use failure::Error;
 use parse::parse_input;
+use rayon::prelude::*;
 use std::cmp::{max, min};
 use std::{collections::HashMap, ops::Range};
 
@@ -119,24 +120,54 @@ impl Map {
         }
     }
 
-    fn map_range(&self, mut range: Range<u64>) -> Vec<Range<u64>> {
+    #[cfg(test)]
+    fn map_value(&self, value: u64) -> u64 {
+        self.ranges
+            .iter()
+            .find(|range| range.src.contains(&value))
+            .map(|range| range.map_value(value))
+            .unwrap_or(value)
+    }
+
+    // All of the values which map to the given value.
+    #[cfg(test)]
+    fn unmap_value(&self, value: u64) -> Vec<u64> {
+        let mut values: Vec<_> = self
+            .ranges
+            .iter()
+            .filter(|range| range.dest.contains(&value))
+            .map(|range| range.src.start + (value - range.dest.start))
+            .collect();
+
+        if !self.ranges.iter().any(|range| range.src.contains(&value)) {
+            values.push(value);
+        }
+
+        values
+    }
+
+    fn map_range(&self, range: Range<u64>) -> Vec<Range<u64>> {
         let mut mapped_ranges = vec![];
+        let mut remaining = Some(range);
 
         for map_range in &self.ranges {
-            let mapping = map_range.map_range(range.clone());
+            let Some(range) = remaining.take() else {
+                break;
+            };
+
+            let mapping = map_range.map_range(range);
             if let Some(before) = mapping.before {
                 mapped_ranges.push(before);
             }
             if let Some(mapped) = mapping.mapped {
                 mapped_ranges.push(mapped)
             }
-            if let Some(after) = mapping.after {
-                range = after;
-            } else {
-                break;
-            }
+            remaining = mapping.after;
         }
 
+        // Anything beyond the last range maps to itself.
+        mapped_ranges.extend(remaining);
+
         mapped_ranges
     }
 }
@@ -167,8 +198,70 @@ impl Almanac {
             .unwrap()
     }
 
+    #[cfg(test)]
+    fn closest_location_with_seed(&self, seed_ranges: bool) -> (u64, u64) {
+        let location = self.get_closest_location(seed_ranges);
+        let seed = self
+            .find_seed(location, seed_ranges)
+            .expect("Closest location should come from a seed");
+        (location, seed)
+    }
+
+    #[cfg(test)]
+    fn get_location(&self, seed: u64) -> u64 {
+        let mut current_type = "seed";
+        let mut value = seed;
+
+        while current_type != "location" {
+            let map = self.maps.get(current_type).unwrap();
+            value = map.map_value(value);
+            current_type = &map.dest;
+        }
+
+        value
+    }
+
+    #[cfg(test)]
+    fn produces_location(&self, seed: u64, location: u64, seed_ranges: bool) -> bool {
+        self.get_seeds(seed_ranges)
+            .iter()
+            .any(|range| range.contains(&seed))
+            && self.get_location(seed) == location
+    }
+
+    // Find a seed within the seed ranges which maps to the given location.
+    #[cfg(test)]
+    fn seed_for_location(&self, location: u64) -> Option<u64> {
+        self.find_seed(location, true)
+    }
+
+    #[cfg(test)]
+    fn find_seed(&self, location: u64, seed_ranges: bool) -> Option<u64> {
+        let mut current_type = "location";
+        let mut values = vec![location];
+
+        while current_type != "seed" {
+            let map = self.maps.values().find(|map| map.dest == current_type)?;
+            values = values
+                .into_iter()
+                .flat_map(|value| map.unmap_value(value))
+                .collect();
+            current_type = &map.source;
+        }
+
+        let seeds = self.get_seeds(seed_ranges);
+        values
+            .into_iter()
+            .filter(|seed| seeds.iter().any(|range| range.contains(seed)))
+            .min()
+    }
+
     fn get_locations(&self, seeds: &[Range<u64>]) -> Vec<Range<u64>> {
-        self.get_items(seeds, "seed", "location")
+        // Each seed range maps independently, so can be done in parallel.
+        seeds
+            .par_iter()
+            .flat_map_iter(|range| self.get_items(std::slice::from_ref(range), "seed", "location"))
+            .collect()
     }
 
     fn get_items(
@@ -190,6 +283,13 @@ impl Almanac {
     }
 }
 
+fn solve_both(almanac: &Almanac) -> (u64, u64) {
+    (
+        almanac.get_closest_location(false),
+        almanac.get_closest_location(true),
+    )
+}
+
 pub struct Solver {}
 
 impl super::Solver for Solver {
@@ -200,8 +300,116 @@ impl super::Solver for Solver {
     }
 
     fn solve(almanac: Self::Problem) -> (Option<String>, Option<String>) {
-        let part1 = almanac.get_closest_location(false);
-        let part2 = almanac.get_closest_location(true);
+        let (part1, part2) = solve_both(&almanac);
         (Some(part1.to_string()), Some(part2.to_string()))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = "seeds: 79 14 55 13
+
+seed-to-soil map:
+50 98 2
+52 50 48
+
+soil-to-fertilizer map:
+0 15 37
+37 52 2
+39 0 15
+
+fertilizer-to-water map:
+49 53 8
+0 11 42
+42 0 7
+57 7 4
+
+water-to-light map:
+88 18 7
+18 25 70
+
+light-to-temperature map:
+45 77 23
+81 45 19
+68 64 13
+
+temperature-to-humidity map:
+0 69 1
+1 0 69
+
+humidity-to-location map:
+60 56 37
+56 93 4
+";
+
+    fn parse() -> Almanac {
+        <Solver as crate::Solver>::parse_input(SAMPLE.to_string()).unwrap()
+    }
+
+    #[test]
+    fn map_range_keeps_values_past_last_entry() {
+        let map = Map::new("seed", "soil", vec![MapRange::new(50, 98, 2)]);
+        assert_eq!(map.map_range(95..110), vec![95..98, 50..52, 100..110]);
+        assert_eq!(map.map_range(200..210), vec![200..210]);
+    }
+
+    #[test]
+    fn solve_both_on_sample() {
+        let almanac = parse();
+        assert_eq!(solve_both(&almanac), (35, 46));
+        for seed in 0..120 {
+            let location = almanac.get_location(seed);
+            let locations = almanac.get_locations(std::slice::from_ref(&(seed..seed + 1)));
+            assert_eq!(locations, vec![location..location + 1]);
+        }
+    }
+
+    #[test]
+    fn parallel_locations_match_serial() {
+        let almanac = parse();
+        for seed_ranges in [false, true] {
+            let seeds = almanac.get_seeds(seed_ranges);
+            let serial = almanac.get_items(&seeds, "seed", "location");
+            assert_eq!(almanac.get_locations(&seeds), serial);
+            assert_eq!(
+                almanac.get_closest_location(seed_ranges),
+                serial.iter().map(|range| range.start).min().unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn produces_location_checks_seed_and_location() {
+        let almanac = parse();
+        assert!(almanac.produces_location(79, 82, false));
+        assert!(!almanac.produces_location(79, 83, false));
+        assert!(almanac.produces_location(82, 46, true));
+        assert!(!almanac.produces_location(82, 46, false));
+    }
+
+    #[test]
+    fn seed_for_location_round_trips() {
+        let almanac = parse();
+        assert_eq!(almanac.seed_for_location(46), Some(82));
+        // Location 35 comes from seed 13, which isn't in any seed range.
+        assert_eq!(almanac.seed_for_location(35), None);
+        for location in 0..100 {
+            if let Some(seed) = almanac.seed_for_location(location) {
+                assert!(almanac.produces_location(seed, location, true));
+            }
+        }
+    }
+
+    #[test]
+    fn closest_location_seed_maps_forward() {
+        let almanac = parse();
+        assert_eq!(almanac.closest_location_with_seed(false), (35, 13));
+        assert_eq!(almanac.closest_location_with_seed(true), (46, 82));
+        for seed_ranges in [false, true] {
+            let (location, seed) = almanac.closest_location_with_seed(seed_ranges);
+            assert_eq!(almanac.get_location(seed), location);
+        }
+    }
+}
@@ -1,4 +1,4 @@
-use crate::parsers::unsigned;
+use crate::parsers::int_list;
 use failure::{err_msg, Error};
 use nom::{
     bytes::complete::tag,
@@ -31,7 +31,8 @@ impl Race {
         // > a = -1
         // > b = t
         // > c = -d
-        let discriminant = (self.time as f64).powi(2) - 4.0 * (-1.0) * (-(self.distance as f64));
+        let (a, b, c) = (-1.0, self.time as f64, -(self.distance as f64));
+        let discriminant = b.powi(2) - 4.0 * a * c;
         if discriminant < 0.0 {
             return 0;
         }
@@ -40,18 +41,31 @@ impl Race {
         // > (-t ± sqrt(discriminant)) / (2.0 * (-1.0))
         // or
         // > (t ± sqrt(discriminant)) / 2.0
+        //
+        // The floating point estimate of the lower root may be out by one for
+        // large races, so correct it by checking the neighbouring presses
+        // exactly.  A press which only ties the record doesn't count.
         let lower = ((self.time as f64) - discriminant.sqrt()) / 2.0;
-        let upper = ((self.time as f64) + discriminant.sqrt()) / 2.0;
+        let mut min_solution = (lower.floor().max(0.0) as u64).min(self.time / 2);
 
-        // Need to find the number of integers > lower and < upper.
-        let min_solution = (lower + 1.0).floor() as u64;
-        let max_solution = (upper - 1.0).ceil() as u64;
+        while min_solution > 0 && self.beats_record(min_solution - 1) {
+            min_solution -= 1;
+        }
+        while min_solution <= self.time / 2 && !self.beats_record(min_solution) {
+            min_solution += 1;
+        }
 
-        if max_solution >= min_solution {
-            max_solution - min_solution + 1
-        } else {
-            0
+        if min_solution > self.time / 2 {
+            return 0;
         }
+
+        // The solutions are symmetric about t / 2.
+        let max_solution = self.time - min_solution;
+        max_solution - min_solution + 1
+    }
+
+    fn beats_record(&self, press: u64) -> bool {
+        (self.time - press) as u128 * press as u128 > self.distance as u128
     }
 }
 
@@ -67,22 +81,26 @@ where
     delimited(tuple((tag(name), tag(":"), space1)), value_parser, newline)
 }
 
-fn numbers(input: &str) -> IResult<&str, Vec<u64>> {
-    separated_list1(space1, unsigned)(input)
-}
-
 fn parse_small_races(data: &str) -> Result<Vec<Race>, Error> {
-    let times = named_value("Time", numbers);
-    let distances = named_value("Distance", numbers);
-    let races = map(tuple((times, distances)), |(ts, ds)| {
-        ts.into_iter()
-            .zip(ds)
-            .map(|(time, distance)| Race { time, distance })
-            .collect()
-    });
-    all_consuming(races)(data)
-        .map(|(_, races)| races)
-        .map_err(|err| err_msg(format!("Failed to parse input: {}", err)))
+    let times = named_value("Time", int_list);
+    let distances = named_value("Distance", int_list);
+    let (ts, ds) = all_consuming(tuple((times, distances)))(data)
+        .map(|(_, lists)| lists)
+        .map_err(|err| err_msg(format!("Failed to parse input: {}", err)))?;
+
+    if ts.len() != ds.len() {
+        return Err(err_msg(format!(
+            "Found {} times but {} distances",
+            ts.len(),
+            ds.len()
+        )));
+    }
+
+    Ok(ts
+        .into_iter()
+        .zip(ds)
+        .map(|(time, distance)| Race { time, distance })
+        .collect())
 }
 
 fn distributed_number(input: &str) -> IResult<&str, u64> {
@@ -103,6 +121,18 @@ fn parse_big_race(data: &str) -> Result<Race, Error> {
         .map_err(|err| err_msg(format!("Failed to parse input: {}", err)))
 }
 
+// The race with the fewest ways to win.
+#[cfg(test)]
+fn closest_race(races: &[Race]) -> Option<&Race> {
+    races.iter().min_by_key(|race| race.ways_to_win())
+}
+
+fn solve_both(small_races: &[Race], big_race: &Race) -> (u64, u64) {
+    let small_ways = small_races.iter().map(|race| race.ways_to_win()).product();
+    let big_ways = big_race.ways_to_win();
+    (small_ways, big_ways)
+}
+
 pub struct Solver {}
 
 impl super::Solver for Solver {
@@ -115,8 +145,87 @@ impl super::Solver for Solver {
     }
 
     fn solve((small_races, big_race): Self::Problem) -> (Option<String>, Option<String>) {
-        let part1: u64 = small_races.iter().map(|race| race.ways_to_win()).product();
-        let part2: u64 = big_race.ways_to_win();
+        let (part1, part2) = solve_both(&small_races, &big_race);
         (Some(part1.to_string()), Some(part2.to_string()))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = "Time:      7  15   30
+Distance:  9  40  200
+";
+
+    fn parse() -> (Vec<Race>, Race) {
+        <Solver as crate::Solver>::parse_input(SAMPLE.to_string()).unwrap()
+    }
+
+    #[test]
+    fn solve_both_on_sample() {
+        let (small_races, big_race) = parse();
+        assert_eq!(solve_both(&small_races, &big_race), (288, 71503));
+    }
+
+    #[test]
+    fn exact_square_discriminants() {
+        // The roots are exactly 3 and 7, which only tie the record.
+        assert_eq!(
+            Race {
+                time: 10,
+                distance: 21
+            }
+            .ways_to_win(),
+            3
+        );
+        assert_eq!(
+            Race {
+                time: 10,
+                distance: 25
+            }
+            .ways_to_win(),
+            0
+        );
+        assert_eq!(
+            Race {
+                time: 8,
+                distance: 12
+            }
+            .ways_to_win(),
+            3
+        );
+
+        for time in 0..60 {
+            for distance in 0..time * time / 4 + 3 {
+                let brute_force = (0..=time)
+                    .filter(|press| (time - press) * press > distance)
+                    .count() as u64;
+                assert_eq!(Race { time, distance }.ways_to_win(), brute_force);
+            }
+        }
+
+        let (time, root) = (200_000_002, 99_999_000);
+        let race = Race {
+            time,
+            distance: root * (time - root),
+        };
+        assert_eq!(race.ways_to_win(), time - 2 * root - 1);
+    }
+
+    #[test]
+    fn mismatched_race_counts() {
+        let data = "Time:      7  15   30\nDistance:  9  40\n";
+        let err = <Solver as crate::Solver>::parse_input(data.to_string())
+            .err()
+            .unwrap();
+        assert_eq!(err.to_string(), "Found 3 times but 2 distances");
+    }
+
+    #[test]
+    fn hardest_race_on_sample() {
+        let (small_races, _) = parse();
+        assert_eq!(closest_race(&small_races).unwrap().time, 7);
+        assert!(closest_race(&[]).is_none());
+    }
+}
@@ -3,33 +3,18 @@ use itertools::Itertools;
 use nom::{
     character::complete::{anychar, newline, space1},
     combinator::{all_consuming, map, map_res},
-    multi::{many1, many_m_n},
+    multi::many1,
     sequence::{separated_pair, terminated},
 };
 
 use crate::parsers::unsigned;
 
-#[derive(PartialEq, Eq, Clone, Copy)]
-enum HandType {
-    HighCard,
-    OnePair,
-    TwoPair,
-    ThreeOfAKind,
-    FullHouse,
-    FourOfAKind,
-    FiveOfAKind,
-}
-
-impl PartialOrd for HandType {
-    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        Some(self.cmp(other))
-    }
-}
-
-impl Ord for HandType {
-    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        (*self as u8).cmp(&(*other as u8))
-    }
+// The number of each kind of card in the hand, largest first. Comparing
+// these lexicographically gives the usual ordering (five of a kind beats four
+// of a kind beats full house etc.) for hands of any size.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct HandType {
+    counts: Vec<usize>,
 }
 
 #[derive(Debug, PartialEq, Eq, Clone)]
@@ -61,35 +46,24 @@ impl Hand {
             counts.push(num_jokers);
         }
 
-        use HandType::*;
-        if counts[0] == 5 {
-            FiveOfAKind
-        } else if counts[0] == 4 {
-            FourOfAKind
-        } else if counts[0] == 3 && counts[1] == 2 {
-            FullHouse
-        } else if counts[0] == 3 {
-            ThreeOfAKind
-        } else if counts[0] == 2 && counts[1] == 2 {
-            TwoPair
-        } else if counts[0] == 2 {
-            OnePair
-        } else {
-            HighCard
-        }
+        HandType { counts }
     }
 
-    fn with_jokers(&self) -> Self {
+    fn with_wildcard(&self, wild: u8) -> Self {
         let cards = self
             .cards
             .iter()
-            .map(|&card| if card == 11 { 0 } else { card })
+            .map(|&card| if card == wild { 0 } else { card })
             .collect();
         Hand {
             cards,
             bid: self.bid,
         }
     }
+
+    fn with_jokers(&self) -> Self {
+        self.with_wildcard(11)
+    }
 }
 
 impl PartialOrd for Hand {
@@ -106,22 +80,37 @@ impl Ord for Hand {
     }
 }
 
-fn find_total_winnings(hands: &[Hand], jokers: bool) -> u64 {
-    let mut hands: Vec<_> = hands
-        .iter()
-        .map(|hand| {
-            if jokers {
-                hand.with_jokers()
-            } else {
-                hand.clone()
-            }
-        })
-        .collect();
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ruleset {
+    Standard,
+    // Jacks are jokers, which count as whatever makes the best hand but are
+    // the weakest card when breaking ties.
+    Jokers,
+}
+
+impl Ruleset {
+    fn apply(self, hand: &Hand) -> Hand {
+        match self {
+            Ruleset::Standard => hand.clone(),
+            Ruleset::Jokers => hand.with_jokers(),
+        }
+    }
+}
+
+pub fn total_winnings(hands: &[Hand], rules: Ruleset) -> u64 {
+    let mut hands: Vec<_> = hands.iter().map(|hand| rules.apply(hand)).collect();
     hands.sort();
 
     (1..).zip(hands).map(|(rank, hand)| hand.bid * rank).sum()
 }
 
+fn solve_both(hands: &[Hand]) -> (u64, u64) {
+    (
+        total_winnings(hands, Ruleset::Standard),
+        total_winnings(hands, Ruleset::Jokers),
+    )
+}
+
 pub struct Solver {}
 
 impl super::Solver for Solver {
@@ -139,21 +128,117 @@ impl super::Solver for Solver {
                 _ => Err(format!("Invalid character for card: {}", c)),
             },
         });
-        let cards = many_m_n(5, 5, card);
+        let cards = many1(card);
         let hand = map(
             terminated(separated_pair(cards, space1, unsigned), newline),
             |(cards, bid)| Hand { cards, bid },
         );
 
-        all_consuming(many1(hand))(&data)
+        let hands: Vec<Hand> = all_consuming(many1(hand))(&data)
             .map(|(_, hand_bids)| hand_bids)
-            .map_err(|err| err_msg(format!("Failed to parse input: {}", err)))
+            .map_err(|err| err_msg(format!("Failed to parse input: {}", err)))?;
+
+        if !hands.iter().map(|hand| hand.cards.len()).all_equal() {
+            return Err(err_msg("Hands must all have the same number of cards"));
+        }
+
+        Ok(hands)
     }
 
     fn solve(hands: Self::Problem) -> (Option<String>, Option<String>) {
-        let part1 = find_total_winnings(&hands, false);
-        let part2 = find_total_winnings(&hands, true);
+        let (part1, part2) = solve_both(&hands);
 
         (Some(part1.to_string()), Some(part2.to_string()))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = "32T3K 765
+T55J5 684
+KK677 28
+KTJJT 220
+QQQJA 483
+";
+
+    fn parse(data: &str) -> Vec<Hand> {
+        <Solver as crate::Solver>::parse_input(data.to_string()).unwrap()
+    }
+
+    #[test]
+    fn solve_both_on_sample() {
+        assert_eq!(solve_both(&parse(SAMPLE)), (6440, 5905));
+    }
+
+    #[test]
+    fn wild_queens() {
+        let hands = parse("QQ234 1\nKQQ2Q 1\n");
+        assert_eq!(
+            hands[0].hand_type(),
+            HandType {
+                counts: vec![2, 1, 1, 1]
+            }
+        );
+        assert_eq!(
+            hands[0].with_wildcard(12).hand_type(),
+            HandType {
+                counts: vec![3, 1, 1]
+            }
+        );
+        assert_eq!(
+            hands[1].with_wildcard(12).hand_type(),
+            HandType { counts: vec![4, 1] }
+        );
+        assert_eq!(hands[0].with_wildcard(12).cards, vec![0, 0, 2, 3, 4]);
+        assert_eq!(hands[0].with_jokers(), hands[0]);
+    }
+
+    #[test]
+    fn six_card_hands() {
+        let hands = parse("KK2K22 5\nAKQJT9 2\n");
+        assert_eq!(hands[0].hand_type(), HandType { counts: vec![3, 3] });
+        assert!(hands[0] > hands[1]);
+        assert!(<Solver as crate::Solver>::parse_input("KK2K22 5\nAKQJT 2\n".to_string()).is_err());
+    }
+
+    #[test]
+    fn hand_type_ordering() {
+        assert!(HandType { counts: vec![5] } > HandType { counts: vec![4, 1] });
+        assert!(
+            HandType { counts: vec![3, 2] }
+                > HandType {
+                    counts: vec![3, 1, 1]
+                }
+        );
+        assert!(
+            HandType {
+                counts: vec![2, 2, 1]
+            } > HandType {
+                counts: vec![2, 1, 1, 1]
+            }
+        );
+        assert!(
+            HandType {
+                counts: vec![2, 1, 1, 1]
+            } > HandType {
+                counts: vec![1, 1, 1, 1, 1]
+            }
+        );
+    }
+
+    #[test]
+    fn rulesets_match_both_parts() {
+        let hands = parse(SAMPLE);
+        let (part1, part2) = <Solver as crate::Solver>::solve(hands.clone());
+        assert_eq!(
+            total_winnings(&hands, Ruleset::Standard).to_string(),
+            part1.unwrap()
+        );
+        assert_eq!(
+            total_winnings(&hands, Ruleset::Jokers).to_string(),
+            part2.unwrap()
+        );
+    }
+}
@@ -1,4 +1,5 @@
 use failure::{err_msg, Error};
+use itertools::Itertools;
 use nom::bytes::complete::tag;
 use nom::character::complete::{alphanumeric1, char, newline};
 use nom::combinator::{all_consuming, map};
@@ -7,7 +8,7 @@ use nom::sequence::{delimited, separated_pair, terminated};
 use nom::IResult;
 use nom::{branch::alt, combinator::value};
 use num::integer::lcm;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 #[derive(Debug, Clone, Copy)]
 pub enum Direction {
@@ -54,11 +55,28 @@ fn path_length(
     directions: &[Direction],
     from: &str,
     to: &str,
-) -> usize {
-    path(locations, directions, from)
-        .take_while(|loc| *loc != to)
-        .count()
-        + 1
+) -> Result<usize, Error> {
+    // Once we're back at the same location at the same point in the
+    // directions we're going round in circles without passing the target.
+    let mut visited = HashSet::new();
+    let mut current = from;
+
+    for (length, (index, direction)) in (1..).zip(directions.iter().enumerate().cycle()) {
+        if !visited.insert((current, index)) {
+            return Err(err_msg(format!("Target {} unreachable from {}", to, from)));
+        }
+
+        let location = locations
+            .get(current)
+            .ok_or_else(|| err_msg(format!("Unknown location {}", current)))?;
+        current = location.get_next(*direction);
+
+        if current == to {
+            return Ok(length);
+        }
+    }
+
+    unreachable!()
 }
 
 fn find_cycle<'a>(dir_cycle: usize, path: impl Iterator<Item = &'a str>) -> (usize, usize) {
@@ -76,16 +94,16 @@ fn find_cycle<'a>(dir_cycle: usize, path: impl Iterator<Item = &'a str>) -> (usi
     unreachable!()
 }
 
-fn find_end_offset<'a, E>(
+fn find_end_offsets<'a, E>(
     cycle_start: usize,
     cycle_len: usize,
     path: impl Iterator<Item = &'a str>,
     end_filter: E,
-) -> usize
+) -> Vec<usize>
 where
     E: Fn(&str) -> bool,
 {
-    let mut offsets = (1..)
+    (1..)
         .zip(path.skip(cycle_start))
         .take(cycle_len)
         .filter_map(|(offset, current)| {
@@ -94,59 +112,113 @@ where
             } else {
                 None
             }
-        });
-
-    let offset = offsets.next().unwrap();
-    assert!(offsets.next().is_none());
-
-    offset
+        })
+        .collect()
 }
 
-fn ghost_path_length<S, E>(
+fn ghost_cycle_info<S, E>(
     locations: &HashMap<String, Location>,
     directions: &[Direction],
     start_filter: &S,
     end_filter: &E,
-) -> usize
+) -> Vec<(String, usize, usize, Vec<usize>)>
 where
     S: Fn(&str) -> bool,
     E: Fn(&str) -> bool,
 {
-    let starts: Vec<_> = locations.keys().filter(|name| start_filter(name)).collect();
-
-    let cycle_lengths: Vec<_> = starts
-        .iter()
-        .map(|start| find_cycle(directions.len(), path(locations, directions, start)))
-        .collect();
-    let offsets: Vec<_> = starts
-        .iter()
-        .zip(cycle_lengths.iter())
-        .map(|(start, (cycle_start, cycle_len))| {
-            find_end_offset(
-                *cycle_start,
-                *cycle_len,
+    locations
+        .keys()
+        .filter(|name| start_filter(name))
+        .sorted()
+        .map(|start| {
+            let (cycle_start, cycle_len) =
+                find_cycle(directions.len(), path(locations, directions, start));
+            let end_offsets = find_end_offsets(
+                cycle_start,
+                cycle_len,
                 path(locations, directions, start),
                 end_filter,
-            )
+            );
+            (start.clone(), cycle_start, cycle_len, end_offsets)
         })
-        .collect();
+        .collect()
+}
+
+// Find a time satisfying both `time1 + n * cycle_len1` and
+// `time2 + m * cycle_len2`, if there is one.
+fn combine_cycles(
+    (time1, cycle_len1): (usize, usize),
+    (time2, cycle_len2): (usize, usize),
+) -> Option<(usize, usize)> {
+    (0..cycle_len2)
+        .map(|n| time1 + n * cycle_len1)
+        .find(|time| time % cycle_len2 == time2 % cycle_len2)
+        .map(|time| (time, lcm(cycle_len1, cycle_len2)))
+}
 
-    cycle_lengths
+// Number of steps until every location matching `start_filter` is
+// simultaneously at a location matching `end_filter`, or None if nothing
+// matches `start_filter` or they never line up.
+pub fn ghost_path_length<S, E>(
+    locations: &HashMap<String, Location>,
+    directions: &[Direction],
+    start_filter: &S,
+    end_filter: &E,
+) -> Option<usize>
+where
+    S: Fn(&str) -> bool,
+    E: Fn(&str) -> bool,
+{
+    let info = ghost_cycle_info(locations, directions, start_filter, end_filter);
+    if info.is_empty() {
+        return None;
+    }
+
+    // Before every ghost has settled into its cycle just walk them all.
+    let settled = info
         .iter()
-        .zip(offsets.iter())
-        .map(|((init, cycle_len), end_offset)| (init + end_offset, *cycle_len))
-        .reduce(|(offset1, cycle_len1), (offset2, cycle_len2)| {
-            let cycle_len = lcm(cycle_len1, cycle_len2);
-            let offset = ((0..).find(|n| {
-                n * cycle_len1 + offset1 > offset2
-                    && (n * cycle_len1 + offset1 - offset2) % cycle_len2 == 0
-            }))
-            .map(|n| n * cycle_len1 + offset1)
-            .unwrap();
-            (offset, cycle_len)
+        .map(|(_, cycle_start, _, _)| *cycle_start)
+        .max()
+        .unwrap();
+    {
+        let mut paths = info
+            .iter()
+            .map(|(start, _, _, _)| path(locations, directions, start))
+            .collect_vec();
+        for time in 1..=settled {
+            let current = paths
+                .iter_mut()
+                .map(|path| path.next().unwrap())
+                .collect_vec();
+            if current.into_iter().all(end_filter) {
+                return Some(time);
+            }
+        }
+    }
+
+    // After that each ghost is at an end at a fixed set of offsets into its
+    // cycle, so find the earliest time that lines up for all of them.
+    info.into_iter()
+        .map(|(_, cycle_start, cycle_len, end_offsets)| {
+            end_offsets
+                .into_iter()
+                .map(|offset| (cycle_start + offset, cycle_len))
+                .collect_vec()
+        })
+        .reduce(|cycles1, cycles2| {
+            cycles1
+                .iter()
+                .cartesian_product(cycles2.iter())
+                .filter_map(|(cycle1, cycle2)| combine_cycles(*cycle1, *cycle2))
+                .collect()
         })
-        .map(|(offset, _)| offset)
         .unwrap()
+        .into_iter()
+        .map(|(time, cycle_len)| {
+            let first = settled + 1;
+            first + (time % cycle_len + cycle_len - first % cycle_len) % cycle_len
+        })
+        .min()
 }
 
 pub struct Solver {}
@@ -185,13 +257,168 @@ impl super::Solver for Solver {
     }
 
     fn solve((directions, locations): Self::Problem) -> (Option<String>, Option<String>) {
-        let part1 = path_length(&locations, &directions, "AAA", "ZZZ");
+        let part1 = path_length(&locations, &directions, "AAA", "ZZZ")
+            .map_or_else(|err| err.to_string(), |len| len.to_string());
         let part2 = ghost_path_length(
             &locations,
             &directions,
             &|name| name.ends_with('A'),
             &|name| name.ends_with('Z'),
         );
-        (Some(part1.to_string()), Some(part2.to_string()))
+        (Some(part1), part2.map(|len| len.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = "LR
+
+11A = (11B, XXX)
+11B = (XXX, 11Z)
+11Z = (11B, XXX)
+22A = (22B, XXX)
+22B = (22C, 22C)
+22C = (22Z, 22Z)
+22Z = (22B, 22B)
+XXX = (XXX, XXX)
+";
+
+    fn parse(data: &str) -> (Vec<Direction>, HashMap<String, Location>) {
+        <Solver as crate::Solver>::parse_input(data.to_string()).unwrap()
+    }
+
+    #[test]
+    fn ghost_cycle_lengths() {
+        let (directions, locations) = parse(SAMPLE);
+        let info = ghost_cycle_info(
+            &locations,
+            &directions,
+            &|name: &str| name.ends_with('A'),
+            &|name: &str| name.ends_with('Z'),
+        );
+        let cycle_lengths: Vec<_> = info
+            .iter()
+            .map(|(start, _, cycle_len, _)| (start.as_str(), *cycle_len))
+            .collect();
+        assert_eq!(cycle_lengths, vec![("11A", 2), ("22A", 6)]);
+    }
+
+    #[test]
+    fn unreachable_target_is_an_error() {
+        let (directions, locations) =
+            parse("LR\n\nAAA = (BBB, AAA)\nBBB = (AAA, BBB)\nZZZ = (ZZZ, ZZZ)\n");
+        assert!(path_length(&locations, &directions, "AAA", "ZZZ").is_err());
+        assert_eq!(
+            <Solver as crate::Solver>::solve((directions, locations)).0,
+            Some("Target ZZZ unreachable from AAA".to_string())
+        );
+    }
+
+    #[test]
+    fn custom_filters() {
+        let (directions, locations) = parse(SAMPLE);
+        assert_eq!(
+            ghost_path_length(
+                &locations,
+                &directions,
+                &|name: &str| name == "11A",
+                &|name: &str| name == "11Z"
+            ),
+            Some(2)
+        );
+        assert_eq!(
+            ghost_path_length(
+                &locations,
+                &directions,
+                &|name: &str| name == "22A",
+                &|name: &str| name == "22Z"
+            ),
+            Some(3)
+        );
+        assert_eq!(
+            ghost_path_length(
+                &locations,
+                &directions,
+                &|name: &str| name == "QQQ",
+                &|name: &str| name == "22Z"
+            ),
+            None
+        );
+    }
+
+    // Walk every ghost one step at a time until they're all at an end.
+    fn brute_force<S, E>(
+        locations: &HashMap<String, Location>,
+        directions: &[Direction],
+        start_filter: &S,
+        end_filter: &E,
+        limit: usize,
+    ) -> Option<usize>
+    where
+        S: Fn(&str) -> bool,
+        E: Fn(&str) -> bool,
+    {
+        let mut paths = locations
+            .keys()
+            .filter(|name| start_filter(name))
+            .map(|start| path(locations, directions, start))
+            .collect_vec();
+        (1..=limit).find(|_| {
+            paths
+                .iter_mut()
+                .map(|path| path.next().unwrap())
+                .collect_vec()
+                .into_iter()
+                .all(end_filter)
+        })
+    }
+
+    #[test]
+    fn two_ends_in_one_cycle() {
+        // A1A reaches E1Z and F1Z within a cycle of five, A2A has a single end
+        // in a cycle of seven and A3A is at an end two steps out of every three.
+        let (directions, locations) = parse(
+            "LR
+
+A1A = (P1Q, P1Q)
+P1Q = (C1Q, C1Q)
+C1Q = (E1Z, E1Z)
+E1Z = (D1Q, D1Q)
+D1Q = (F1Z, F1Z)
+F1Z = (G1Q, G1Q)
+G1Q = (C1Q, C1Q)
+A2A = (B2Q, B2Q)
+B2Q = (C2Q, C2Q)
+C2Q = (D2Z, D2Z)
+D2Z = (E2Q, E2Q)
+E2Q = (F2Q, F2Q)
+F2Q = (G2Q, G2Q)
+G2Q = (H2Q, H2Q)
+H2Q = (B2Q, B2Q)
+A3A = (A3Z, A3Z)
+A3Z = (B3Z, B3Z)
+B3Z = (C3Q, C3Q)
+C3Q = (A3Z, A3Z)
+",
+        );
+        let end_filter = |name: &str| name.ends_with('Z');
+
+        for starts in [["A1A", "A2A"], ["A1A", "A3A"], ["A2A", "A3A"]] {
+            let start_filter = |name: &str| starts.contains(&name);
+            assert_eq!(
+                ghost_path_length(&locations, &directions, &start_filter, &end_filter),
+                brute_force(&locations, &directions, &start_filter, &end_filter, 1000)
+            );
+        }
+
+        let start_filter = |name: &str| name.ends_with('A');
+        let expected = brute_force(&locations, &directions, &start_filter, &end_filter, 1000);
+        assert!(expected.is_some());
+        assert_eq!(
+            ghost_path_length(&locations, &directions, &start_filter, &end_filter),
+            expected
+        );
     }
 }
@@ -1,31 +1,103 @@
+use crate::parsers::signed;
 use failure::{err_msg, Error};
 use itertools::Itertools;
+use nom::{
+    character::complete::{newline, space1},
+    combinator::all_consuming,
+    multi::{many1, separated_list1},
+    sequence::terminated,
+};
+#[cfg(test)]
+use num::integer::binomial;
+use std::iter::successors;
+
+fn differences(values: &[i64]) -> Vec<i64> {
+    values.iter().tuple_windows().map(|(x, y)| y - x).collect()
+}
+
+// A sequence is polynomial if its differences reach a row of zeros, where a
+// row with no values counts as zeros.  The rows are checked down to the one
+// with a single value, but always as far as the second differences, so any
+// sequence of fewer than three values is a constant or a line.
+fn is_polynomial(values: &[i64]) -> bool {
+    successors(Some(values.to_vec()), |row| Some(differences(row)))
+        .take(values.len().max(3))
+        .any(|row| row.iter().all(|val| *val == 0))
+}
+
+// The `steps_before` values preceding the sequence and the `steps_after`
+// values following it, each in sequence order.
+fn extrapolate(
+    values: &[i64],
+    steps_before: usize,
+    steps_after: usize,
+) -> Result<(Vec<i64>, Vec<i64>), Error> {
+    if !is_polynomial(values) {
+        return Err(err_msg(format!("Sequence is not polynomial: {:?}", values)));
+    }
 
-fn find_prev_next_value(values: &[i64]) -> (i64, i64) {
     let mut stack: Vec<Vec<i64>> = vec![values.to_vec()];
     while !stack.last().unwrap().iter().all(|val| *val == 0) {
-        stack.push(
-            stack
-                .last()
-                .unwrap()
-                .iter()
-                .tuple_windows()
-                .map(|(x, y)| y - x)
-                .collect(),
-        );
+        stack.push(differences(stack.last().unwrap()));
     }
 
-    stack.last_mut().unwrap().push(0);
-    stack.last_mut().unwrap().insert(0, 0);
+    for _ in 0..steps_before {
+        stack.last_mut().unwrap().insert(0, 0);
+        for index in (0..stack.len() - 1).rev() {
+            let start_val = stack[index].first().unwrap() - stack[index + 1].first().unwrap();
+            stack[index].insert(0, start_val);
+        }
+    }
 
-    for index in (0..stack.len() - 1).rev() {
-        let start_val = stack[index].first().unwrap() - stack[index + 1].first().unwrap();
-        stack[index].insert(0, start_val);
-        let end_val = stack[index].last().unwrap() + stack[index + 1].last().unwrap();
-        stack[index].push(end_val);
+    for _ in 0..steps_after {
+        stack.last_mut().unwrap().push(0);
+        for index in (0..stack.len() - 1).rev() {
+            let end_val = stack[index].last().unwrap() + stack[index + 1].last().unwrap();
+            stack[index].push(end_val);
+        }
     }
 
-    (*stack[0].first().unwrap(), *stack[0].last().unwrap())
+    let mut values = stack.swap_remove(0);
+    let after = values.split_off(values.len() - steps_after);
+    values.truncate(steps_before);
+
+    Ok((values, after))
+}
+
+fn find_prev_next_value(values: &[i64]) -> Result<(i64, i64), Error> {
+    let (before, after) = extrapolate(values, 1, 1)?;
+    Ok((before[0], after[0]))
+}
+
+// Independent check of `find_prev_next_value`: fit the polynomial through
+// the values using Newton's forward differences and evaluate it either side.
+#[cfg(test)]
+fn extrapolate_poly(values: &[i64]) -> (i64, i64) {
+    let leading: Vec<i64> = successors(Some(values.to_vec()), |current| {
+        Some(differences(current)).filter(|next| !next.is_empty())
+    })
+    .map(|current| current[0])
+    .collect();
+
+    let n = values.len() as i64;
+    let prev = (0..)
+        .zip(leading.iter())
+        .map(|(k, diff)| if k % 2 == 0 { *diff } else { -diff })
+        .sum();
+    let next = (0..)
+        .zip(leading.iter())
+        .map(|(k, diff)| diff * binomial(n, k))
+        .sum();
+
+    (prev, next)
+}
+
+fn solve_both(series: &[Vec<i64>]) -> (i64, i64) {
+    let (prev, next) = series
+        .iter()
+        .map(|values| find_prev_next_value(values).unwrap())
+        .fold((0, 0), |(tot_x, tot_y), (x, y)| (tot_x + x, tot_y + y));
+    (next, prev)
 }
 
 pub struct Solver {}
@@ -34,23 +106,113 @@ impl super::Solver for Solver {
     type Problem = Vec<Vec<i64>>;
 
     fn parse_input(data: String) -> Result<Self::Problem, Error> {
-        data.lines()
-            .map(|line| {
-                line.split_ascii_whitespace()
-                    .map(|val| {
-                        val.parse()
-                            .map_err(|err| err_msg(format!("Invalid number {}: {}", val, err)))
-                    })
-                    .collect()
-            })
-            .collect()
+        let series: Vec<Vec<i64>> =
+            all_consuming(many1(terminated(separated_list1(space1, signed), newline)))(&data)
+                .map(|(_, series)| series)
+                .map_err(|err| err_msg(format!("Failed to parse input: {}", err)))?;
+
+        if let Some(index) = series.iter().position(|values| !is_polynomial(values)) {
+            return Err(err_msg(format!(
+                "Sequence on line {} is not polynomial",
+                index + 1
+            )));
+        }
+
+        Ok(series)
     }
 
     fn solve(series: Self::Problem) -> (Option<String>, Option<String>) {
-        let (part2, part1) = series
-            .iter()
-            .map(|values| find_prev_next_value(values))
-            .fold((0, 0), |(tot_x, tot_y), (x, y)| (tot_x + x, tot_y + y));
+        let (part1, part2) = solve_both(&series);
         (Some(part1.to_string()), Some(part2.to_string()))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn short_sequences_are_polynomial() {
+        assert!(is_polynomial(&[5]));
+        assert!(is_polynomial(&[1, 2]));
+        assert!(is_polynomial(&[1, 2, 3]));
+        assert!(!is_polynomial(&[1, 2, 4]));
+        assert_eq!(find_prev_next_value(&[5]).unwrap(), (5, 5));
+        assert_eq!(find_prev_next_value(&[1, 2]).unwrap(), (0, 3));
+    }
+
+    #[test]
+    fn geometric_series_is_not_polynomial() {
+        assert!(!is_polynomial(&[1, 2, 4, 8, 16, 32]));
+        assert!(find_prev_next_value(&[1, 2, 4, 8, 16, 32]).is_err());
+        assert!(<Solver as crate::Solver>::parse_input("1 3 9 27 81\n".to_string()).is_err());
+    }
+
+    const SAMPLE: &str = "0 3 6 9 12 15
+1 3 6 10 15 21
+10 13 16 21 30 45
+";
+
+    fn parse(data: &str) -> Vec<Vec<i64>> {
+        <Solver as crate::Solver>::parse_input(data.to_string()).unwrap()
+    }
+
+    #[test]
+    fn negative_values_parse() {
+        assert_eq!(solve_both(&parse(SAMPLE)), (114, 2));
+        assert_eq!(solve_both(&parse("-1 -2 -3\n")), (-4, 0));
+    }
+
+    #[test]
+    fn newton_extrapolation_matches_differences() {
+        for values in parse(SAMPLE) {
+            assert_eq!(
+                extrapolate_poly(&values),
+                find_prev_next_value(&values).unwrap()
+            );
+        }
+
+        // Values of random polynomials of increasing degree.
+        let mut seed = 7u64;
+        for degree in 0..6 {
+            for _ in 0..20 {
+                let coefficients: Vec<i64> = (0..=degree)
+                    .map(|_| {
+                        seed = seed
+                            .wrapping_mul(6364136223846793005)
+                            .wrapping_add(1442695040888963407);
+                        ((seed >> 33) % 41) as i64 - 20
+                    })
+                    .collect();
+                let values: Vec<i64> = (0..degree as i64 + 4)
+                    .map(|x| coefficients.iter().rev().fold(0, |acc, c| acc * x + c))
+                    .collect();
+                assert_eq!(
+                    extrapolate_poly(&values),
+                    find_prev_next_value(&values).unwrap()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn extrapolate_several_steps() {
+        assert_eq!(
+            extrapolate(&[1, 4, 9, 16], 0, 3).unwrap(),
+            (vec![], vec![25, 36, 49])
+        );
+        assert_eq!(
+            extrapolate(&[1, 4, 9, 16], 3, 0).unwrap(),
+            (vec![4, 1, 0], vec![])
+        );
+        assert_eq!(
+            extrapolate(&[10, 13, 16, 21, 30, 45], 2, 2).unwrap(),
+            (vec![-4, 5], vec![68, 101])
+        );
+    }
+
+    #[test]
+    fn solve_both_on_sample() {
+        assert_eq!(solve_both(&parse(SAMPLE)), (114, 2));
+    }
+}
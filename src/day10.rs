@@ -47,7 +47,22 @@ impl TryFrom<char> for Pipe {
     }
 }
 
-fn find_loop(start: Position, pipes: &mut HashMap<Position, Pipe>) -> HashSet<Position> {
+fn has_valid_loop(start: Position, pipes: &HashMap<Position, Pipe>) -> bool {
+    Direction::all()
+        .filter(|dir| {
+            pipes
+                .get(&start.step(*dir))
+                .map(|pipe| pipe.has_direction(dir.reverse()))
+                .unwrap_or_default()
+        })
+        .count()
+        >= 2
+}
+
+fn find_loop(
+    start: Position,
+    pipes: &mut HashMap<Position, Pipe>,
+) -> Result<HashSet<Position>, Error> {
     let mut current: Vec<_> = Direction::all().map(|dir| (vec![start], dir)).collect();
 
     loop {
@@ -62,7 +77,9 @@ fn find_loop(start: Position, pipes: &mut HashMap<Position, Pipe>) -> HashSet<Po
             }
         });
 
-        assert!(current.len() > 1);
+        if current.len() < 2 {
+            return Err(err_msg("No loop through start"));
+        }
 
         for i in 0..current.len() {
             let this_route = &current[i].0;
@@ -76,11 +93,11 @@ fn find_loop(start: Position, pipes: &mut HashMap<Position, Pipe>) -> HashSet<Po
                         ),
                     );
 
-                    return this_route
+                    return Ok(this_route
                         .iter()
                         .chain(other_route.iter())
                         .cloned()
-                        .collect();
+                        .collect());
                 }
             }
         }
@@ -91,23 +108,31 @@ fn find_furthest_distance(pipe_loop: &HashSet<Position>) -> usize {
     pipe_loop.len() / 2
 }
 
-fn find_spaces_inside(pipes: &HashMap<Position, Pipe>, pipe_loop: &HashSet<Position>) -> usize {
-    use Direction::*;
-
-    let mut total = 0;
-
-    let (min_x, max_x) = pipes
+fn bounds(pipes: &HashMap<Position, Pipe>) -> ((i64, i64), (i64, i64)) {
+    let x_range = pipes
         .keys()
         .map(|pos| pos.x)
         .minmax()
         .into_option()
         .unwrap();
-    let (min_y, max_y) = pipes
+    let y_range = pipes
         .keys()
         .map(|pos| pos.y)
         .minmax()
         .into_option()
         .unwrap();
+    (x_range, y_range)
+}
+
+fn find_inside(
+    pipes: &HashMap<Position, Pipe>,
+    pipe_loop: &HashSet<Position>,
+) -> HashSet<Position> {
+    use Direction::*;
+
+    let mut inside = HashSet::new();
+
+    let ((min_x, max_x), (min_y, max_y)) = bounds(pipes);
 
     for y in min_y..=max_y {
         let mut num_north = 0;
@@ -119,12 +144,51 @@ fn find_spaces_inside(pipes: &HashMap<Position, Pipe>, pipe_loop: &HashSet<Posit
                     num_north += 1;
                 }
             } else if num_north % 2 == 1 {
-                total += 1;
+                inside.insert(pos);
             }
         }
     }
 
-    total
+    inside
+}
+
+fn find_spaces_inside(pipes: &HashMap<Position, Pipe>, pipe_loop: &HashSet<Position>) -> usize {
+    find_inside(pipes, pipe_loop).len()
+}
+
+#[cfg(test)]
+fn render(pipes: &HashMap<Position, Pipe>, pipe_loop: &HashSet<Position>) -> String {
+    let inside = find_inside(pipes, pipe_loop);
+    let ((min_x, max_x), (min_y, max_y)) = bounds(pipes);
+
+    let mut output = String::new();
+    for y in min_y..=max_y {
+        for x in min_x..=max_x {
+            let pos = Position { x, y };
+            output.push(if pipe_loop.contains(&pos) {
+                '█'
+            } else if inside.contains(&pos) {
+                'I'
+            } else {
+                '.'
+            });
+        }
+        output.push('\n');
+    }
+
+    output
+}
+
+fn analyze_loop(
+    start: Position,
+    mut pipes: HashMap<Position, Pipe>,
+) -> Result<(usize, usize), Error> {
+    let pipe_loop = find_loop(start, &mut pipes)?;
+
+    Ok((
+        find_furthest_distance(&pipe_loop),
+        find_spaces_inside(&pipes, &pipe_loop),
+    ))
 }
 
 pub struct Solver {}
@@ -168,14 +232,104 @@ impl super::Solver for Solver {
             })
             .collect();
 
+        if !has_valid_loop(start, &pipes) {
+            return Err(err_msg("Start position is not connected to a loop"));
+        }
+
         Ok((start, pipes))
     }
 
-    fn solve((start, mut pipes): Self::Problem) -> (Option<String>, Option<String>) {
-        let pipe_loop = find_loop(start, &mut pipes);
+    fn solve((start, pipes): Self::Problem) -> (Option<String>, Option<String>) {
+        match analyze_loop(start, pipes) {
+            Ok((part1, part2)) => (Some(part1.to_string()), Some(part2.to_string())),
+            Err(err) => (Some(err.to_string()), Some(err.to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE1: &str = "..F7.
+.FJ|.
+SJ.L7
+|F--J
+LJ...
+";
+
+    const SAMPLE2: &str = "...........
+.S-------7.
+.|F-----7|.
+.||.....||.
+.||.....||.
+.|L-7.F-J|.
+.|..|.|..|.
+.L--J.L--J.
+...........
+";
+
+    // Parse the pipes without checking the start is on a loop.
+    fn parse_pipes(data: &str) -> (Position, HashMap<Position, Pipe>) {
+        let start = data
+            .lines()
+            .enumerate()
+            .find_map(|(y, line)| line.find('S').map(|x| (x, y).into()))
+            .unwrap();
+        let pipes = data
+            .lines()
+            .enumerate()
+            .flat_map(|(y, line)| {
+                line.chars().enumerate().filter_map(move |(x, c)| {
+                    Pipe::try_from(c).ok().map(|pipe| ((x, y).into(), pipe))
+                })
+            })
+            .collect();
+        (start, pipes)
+    }
+
+    #[test]
+    fn start_with_one_connection_has_no_loop() {
+        let (start, mut pipes) = parse_pipes(".....\n.S-7.\n.....\n");
+        assert!(find_loop(start, &mut pipes).is_err());
+
+        // Both neighbours lead into the start, but they don't join up.
+        let problem =
+            <Solver as crate::Solver>::parse_input(".....\n.S-7.\n.|...\n.....\n".to_string())
+                .unwrap();
+        assert_eq!(
+            <Solver as crate::Solver>::solve(problem).0,
+            Some("No loop through start".to_string())
+        );
+    }
+
+    #[test]
+    fn one_valid_neighbour_is_not_a_loop() {
+        let (start, pipes) = parse_pipes(".....\n.S-7.\n.|...\n.....\n");
+        assert!(has_valid_loop(start, &pipes));
+        let (start, pipes) = parse_pipes(".....\n.S-7.\n.....\n");
+        assert!(!has_valid_loop(start, &pipes));
+        assert!(
+            <Solver as crate::Solver>::parse_input(".....\n.S-7.\n.....\n".to_string()).is_err()
+        );
+    }
+
+    #[test]
+    fn render_marks_inside_spaces() {
+        let (start, mut pipes) = parse_pipes(SAMPLE2);
+        let pipe_loop = find_loop(start, &mut pipes).unwrap();
+        let rendered = render(&pipes, &pipe_loop);
+        assert_eq!(
+            rendered.chars().filter(|c| *c == 'I').count(),
+            find_spaces_inside(&pipes, &pipe_loop)
+        );
+    }
 
-        let part1 = find_furthest_distance(&pipe_loop);
-        let part2 = find_spaces_inside(&pipes, &pipe_loop);
-        (Some(part1.to_string()), Some(part2.to_string()))
+    #[test]
+    fn analyze_loop_on_samples() {
+        let (start, pipes) = parse_pipes(SAMPLE1);
+        assert_eq!(analyze_loop(start, pipes).unwrap().0, 8);
+        let (start, pipes) = parse_pipes(SAMPLE2);
+        assert_eq!(analyze_loop(start, pipes).unwrap(), (23, 4));
     }
 }
@@ -1,8 +1,14 @@
-use crate::common::Position;
+use crate::{common::Position, parsers::char_grid};
 use failure::Error;
 use itertools::Itertools;
 
-fn get_total_lengths(galaxies: &Vec<Position>, expansion: usize) -> usize {
+// Sum of the distances between every pair of galaxies, where each empty row
+// and column is replaced by `expansion` of them (so 2 means doubled).
+pub fn sum_of_distances(galaxies: &[Position], expansion: usize) -> usize {
+    total_lengths_threshold(galaxies, expansion, 0)
+}
+
+fn total_lengths_threshold(galaxies: &[Position], expansion: usize, max_density: usize) -> usize {
     let mut total = 0;
 
     let (min_x, max_x) = galaxies
@@ -17,14 +23,15 @@ fn get_total_lengths(galaxies: &Vec<Position>, expansion: usize) -> usize {
 
     for x in min_x..=max_x {
         let num_in_col = galaxies.iter().filter(|pos| pos.x == x).count();
-
-        if num_in_col == 0 {
-            total += expansion * right * left;
+        let width = if num_in_col <= max_density {
+            expansion
         } else {
-            total += right * left;
-            right -= num_in_col;
-            left += num_in_col;
-        }
+            1
+        };
+
+        total += width * right * left;
+        right -= num_in_col;
+        left += num_in_col;
     }
     let (min_y, max_y) = galaxies
         .iter()
@@ -38,46 +45,87 @@ fn get_total_lengths(galaxies: &Vec<Position>, expansion: usize) -> usize {
 
     for y in min_y..=max_y {
         let num_in_row = galaxies.iter().filter(|pos| pos.y == y).count();
-
-        if num_in_row == 0 {
-            total += expansion * below * above;
+        let height = if num_in_row <= max_density {
+            expansion
         } else {
-            total += below * above;
-            below -= num_in_row;
-            above += num_in_row;
-        }
+            1
+        };
+
+        total += height * below * above;
+        below -= num_in_row;
+        above += num_in_row;
     }
 
     total
 }
 
+fn solve_both(galaxies: &[Position], factor1: usize, factor2: usize) -> (usize, usize) {
+    (
+        sum_of_distances(galaxies, factor1),
+        sum_of_distances(galaxies, factor2),
+    )
+}
+
 pub struct Solver {}
 
 impl super::Solver for Solver {
     type Problem = Vec<Position>;
 
     fn parse_input(data: String) -> Result<Self::Problem, Error> {
-        Ok(data
-            .lines()
-            .enumerate()
-            .flat_map(|(y, line)| {
-                line.chars().enumerate().filter_map(move |(x, c)| {
-                    if c == '#' {
-                        Some(Position {
-                            x: x as i64,
-                            y: y as i64,
-                        })
-                    } else {
-                        None
-                    }
-                })
-            })
+        Ok(char_grid(&data, |c| (c == '#').then_some(()))
+            .into_keys()
             .collect())
     }
 
     fn solve(galaxies: Self::Problem) -> (Option<String>, Option<String>) {
-        let part1 = get_total_lengths(&galaxies, 2);
-        let part2 = get_total_lengths(&galaxies, 1000000);
+        let (part1, part2) = solve_both(&galaxies, 2, 1000000);
         (Some(part1.to_string()), Some(part2.to_string()))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = "...#......
+.......#..
+#.........
+..........
+......#...
+.#........
+.........#
+..........
+.......#..
+#...#.....
+";
+
+    fn parse() -> Vec<Position> {
+        <Solver as crate::Solver>::parse_input(SAMPLE.to_string()).unwrap()
+    }
+
+    #[test]
+    fn threshold_expands_sparse_lines() {
+        // Every row and column except x = 2, which has two galaxies, is
+        // tripled, giving distances of 7, 13 and 6.
+        let galaxies = [
+            Position { x: 0, y: 0 },
+            Position { x: 2, y: 1 },
+            Position { x: 2, y: 3 },
+        ];
+        assert_eq!(total_lengths_threshold(&galaxies, 3, 1), 26);
+        assert_eq!(total_lengths_threshold(&galaxies, 3, 0), 18);
+    }
+
+    #[test]
+    fn sample_expansion_factors() {
+        let galaxies = parse();
+        assert_eq!(sum_of_distances(&galaxies, 2), 374);
+        assert_eq!(sum_of_distances(&galaxies, 10), 1030);
+        assert_eq!(sum_of_distances(&galaxies, 100), 8410);
+    }
+
+    #[test]
+    fn solve_both_with_two_factors() {
+        assert_eq!(solve_both(&parse(), 2, 10), (374, 1030));
+    }
+}
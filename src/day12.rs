@@ -8,7 +8,7 @@ use nom::{
     multi::{many1, separated_list1},
     sequence::{separated_pair, terminated},
 };
-use std::cmp::min;
+use std::{cmp::min, collections::HashMap};
 
 #[derive(PartialEq, Eq, Debug, Clone, Copy)]
 enum Spring {
@@ -59,6 +59,7 @@ fn group_match_len(springs: &[Spring], group_size: usize) -> Option<usize> {
     }
 }
 
+#[cfg(test)]
 #[derive(PartialEq, Eq, Debug)]
 struct State {
     spring_offset: usize,
@@ -66,12 +67,14 @@ struct State {
     combinations: usize,
 }
 
+#[cfg(test)]
 impl PartialOrd for State {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
         Some(self.cmp(other))
     }
 }
 
+#[cfg(test)]
 impl Ord for State {
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
         self.spring_offset
@@ -80,6 +83,7 @@ impl Ord for State {
     }
 }
 
+#[cfg(test)]
 fn get_num_arragements(line: &Line) -> usize {
     let mut candidates = vec![State {
         spring_offset: 0,
@@ -150,6 +154,151 @@ fn get_num_arragements(line: &Line) -> usize {
     total
 }
 
+fn count_arrangements_memo(springs: &[Spring], groups: &[usize]) -> usize {
+    fn count(
+        springs: &[Spring],
+        groups: &[usize],
+        spring_offset: usize,
+        group_offset: usize,
+        cache: &mut HashMap<(usize, usize), usize>,
+    ) -> usize {
+        if let Some(combinations) = cache.get(&(spring_offset, group_offset)) {
+            return *combinations;
+        }
+
+        let remaining = &springs[spring_offset..];
+
+        let combinations = if group_offset == groups.len() {
+            if remaining.iter().all(|spring| *spring != Spring::Damaged) {
+                1
+            } else {
+                0
+            }
+        } else if remaining.is_empty() {
+            0
+        } else {
+            let mut combinations = 0;
+
+            if remaining[0] != Spring::Damaged {
+                combinations += count(springs, groups, spring_offset + 1, group_offset, cache);
+            }
+
+            if remaining[0] != Spring::Operational {
+                if let Some(match_len) = group_match_len(remaining, groups[group_offset]) {
+                    combinations += count(
+                        springs,
+                        groups,
+                        spring_offset + match_len,
+                        group_offset + 1,
+                        cache,
+                    );
+                }
+            }
+
+            combinations
+        };
+
+        cache.insert((spring_offset, group_offset), combinations);
+        combinations
+    }
+
+    count(springs, groups, 0, 0, &mut HashMap::new())
+}
+
+// Count the ways to fill in the unknown springs such that no run of damaged
+// springs is longer than `max_run`, ignoring the line's groups.
+#[cfg(test)]
+fn count_with_max_run(line: &Line, max_run: usize) -> usize {
+    // Number of fillings so far, indexed by the length of the current run of
+    // damaged springs.
+    let mut counts = vec![0; max_run + 1];
+    counts[0] = 1;
+
+    for spring in line.springs.iter() {
+        let mut next_counts = vec![0; max_run + 1];
+
+        for (run, count) in counts.iter().enumerate() {
+            if *spring != Spring::Damaged {
+                next_counts[0] += count;
+            }
+            if *spring != Spring::Operational && run < max_run {
+                next_counts[run + 1] += count;
+            }
+        }
+
+        counts = next_counts;
+    }
+
+    counts.iter().sum()
+}
+
+// Enumerating every arrangement is exponential in the number of unknown
+// springs, so this gives up on lines with more than this, such as unfolded ones.
+#[cfg(test)]
+const MAX_ENUMERATED_UNKNOWNS: usize = 20;
+
+#[cfg(test)]
+fn arrangements(line: &Line) -> Option<Vec<Vec<Spring>>> {
+    fn fill(
+        springs: &[Spring],
+        groups: &[usize],
+        prefix: &mut Vec<Spring>,
+        results: &mut Vec<Vec<Spring>>,
+    ) {
+        if groups.is_empty() {
+            if springs.iter().all(|spring| *spring != Spring::Damaged) {
+                let mut result = prefix.clone();
+                result.extend(springs.iter().map(|_| Spring::Operational));
+                results.push(result);
+            }
+            return;
+        }
+
+        if springs.is_empty() {
+            return;
+        }
+
+        if springs[0] != Spring::Damaged {
+            prefix.push(Spring::Operational);
+            fill(&springs[1..], groups, prefix, results);
+            prefix.pop();
+        }
+
+        if springs[0] != Spring::Operational {
+            if let Some(match_len) = group_match_len(springs, groups[0]) {
+                let prefix_len = prefix.len();
+                prefix.extend((0..groups[0]).map(|_| Spring::Damaged));
+                prefix.extend((groups[0]..match_len).map(|_| Spring::Operational));
+                fill(&springs[match_len..], &groups[1..], prefix, results);
+                prefix.truncate(prefix_len);
+            }
+        }
+    }
+
+    let num_unknown = line
+        .springs
+        .iter()
+        .filter(|spring| **spring == Spring::Unknown)
+        .count();
+    if num_unknown > MAX_ENUMERATED_UNKNOWNS {
+        return None;
+    }
+
+    let mut results = vec![];
+    fill(&line.springs, &line.groups, &mut vec![], &mut results);
+    Some(results)
+}
+
+#[cfg(test)]
+fn max_possible_arrangements(line: &Line) -> usize {
+    let num_unknown = line
+        .springs
+        .iter()
+        .filter(|spring| **spring == Spring::Unknown)
+        .count();
+    2_usize.saturating_pow(num_unknown as u32)
+}
+
 pub struct Solver {}
 
 impl super::Solver for Solver {
@@ -178,9 +327,131 @@ impl super::Solver for Solver {
     }
 
     fn solve(lines: Self::Problem) -> (Option<String>, Option<String>) {
-        let part1: usize = lines.iter().map(get_num_arragements).sum();
+        let part1: usize = lines
+            .iter()
+            .map(|line| count_arrangements_memo(&line.springs, &line.groups))
+            .sum();
         let unfolded_lines: Vec<_> = lines.iter().map(Line::unfold).collect();
-        let part2: usize = unfolded_lines.iter().map(get_num_arragements).sum();
+        let part2: usize = unfolded_lines
+            .iter()
+            .map(|line| count_arrangements_memo(&line.springs, &line.groups))
+            .sum();
         (Some(part1.to_string()), Some(part2.to_string()))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = "???.### 1,1,3
+.??..??...?##. 1,1,3
+?#?#?#?#?#?#?#? 1,3,1,6
+????.#...#... 4,1,1
+????.######..#####. 1,6,5
+?###???????? 3,2,1
+";
+
+    fn parse(data: &str) -> Vec<Line> {
+        <Solver as crate::Solver>::parse_input(data.to_string()).unwrap()
+    }
+
+    #[test]
+    fn memo_count_matches_candidate_search() {
+        for line in parse(SAMPLE) {
+            assert_eq!(
+                count_arrangements_memo(&line.springs, &line.groups),
+                get_num_arragements(&line)
+            );
+            let unfolded = line.unfold();
+            assert_eq!(
+                count_arrangements_memo(&unfolded.springs, &unfolded.groups),
+                get_num_arragements(&unfolded)
+            );
+        }
+    }
+
+    #[test]
+    fn solve_on_sample() {
+        assert_eq!(
+            <Solver as crate::Solver>::solve(parse(SAMPLE)),
+            (Some("21".to_string()), Some("525152".to_string()))
+        );
+    }
+
+    #[test]
+    fn arrangements_match_count() {
+        for line in parse(SAMPLE) {
+            let arrangements = arrangements(&line).unwrap();
+            assert_eq!(
+                arrangements.len(),
+                count_arrangements_memo(&line.springs, &line.groups)
+            );
+            for arrangement in arrangements {
+                assert!(arrangement
+                    .iter()
+                    .zip(line.springs.iter())
+                    .all(|(filled, spring)| *spring == Spring::Unknown || filled == spring));
+                let groups: Vec<usize> = arrangement
+                    .split(|spring| *spring == Spring::Operational)
+                    .map(<[Spring]>::len)
+                    .filter(|len| *len > 0)
+                    .collect();
+                assert_eq!(groups, line.groups);
+            }
+        }
+    }
+
+    #[test]
+    fn arrangements_gives_up_on_unfolded_lines() {
+        let line = parse(SAMPLE)[5].unfold();
+        assert!(arrangements(&line).is_none());
+    }
+
+    // Count the fillings of the unknown springs by trying every one.
+    fn brute_force_max_run(springs: &[Spring], max_run: usize) -> usize {
+        let unknown: Vec<usize> = (0..springs.len())
+            .filter(|index| springs[*index] == Spring::Unknown)
+            .collect();
+
+        (0..1u32 << unknown.len())
+            .filter(|mask| {
+                let mut filled = springs.to_vec();
+                for (bit, index) in unknown.iter().enumerate() {
+                    filled[*index] = if mask >> bit & 1 == 1 {
+                        Spring::Damaged
+                    } else {
+                        Spring::Operational
+                    };
+                }
+                filled
+                    .split(|spring| *spring == Spring::Operational)
+                    .all(|run| run.len() <= max_run)
+            })
+            .count()
+    }
+
+    #[test]
+    fn count_with_max_run_matches_brute_force() {
+        for line in parse(SAMPLE) {
+            for max_run in 0..5 {
+                assert_eq!(
+                    count_with_max_run(&line, max_run),
+                    brute_force_max_run(&line.springs, max_run)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn count_bounded_by_max_possible() {
+        let lines = parse(SAMPLE);
+        for line in &lines {
+            assert!(
+                count_arrangements_memo(&line.springs, &line.groups)
+                    <= max_possible_arrangements(line)
+            );
+        }
+        assert_eq!(max_possible_arrangements(&lines[0]), 8);
+    }
+}
@@ -44,6 +44,13 @@ impl Grid {
     fn rows_before_reflection(&self, num_change: usize) -> Option<usize> {
         find_reflection(&self.rows, num_change)
     }
+
+    pub fn reflection_score(&self, num_change: usize) -> Option<usize> {
+        self.cols_before_reflection(num_change).or_else(|| {
+            self.rows_before_reflection(num_change)
+                .map(|rows| 100 * rows)
+        })
+    }
 }
 
 impl FromStr for Grid {
@@ -70,21 +77,48 @@ impl super::Solver for Solver {
     fn solve(grids: Self::Problem) -> (Option<String>, Option<String>) {
         let part1: usize = grids
             .iter()
-            .map(|grid| {
-                grid.cols_before_reflection(0)
-                    .or_else(|| grid.rows_before_reflection(0).map(|rows| 100 * rows))
-                    .unwrap()
-            })
+            .map(|grid| grid.reflection_score(0).unwrap())
             .sum();
         let part2: usize = grids
             .iter()
-            .map(|grid| {
-                grid.cols_before_reflection(1)
-                    .or_else(|| grid.rows_before_reflection(1).map(|rows| 100 * rows))
-                    .unwrap()
-            })
+            .map(|grid| grid.reflection_score(1).unwrap())
             .sum();
 
         (Some(part1.to_string()), Some(part2.to_string()))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = "#.##..##.
+..#.##.#.
+##......#
+##......#
+..#.##.#.
+..##..##.
+#.#.##.#.
+
+#...##..#
+#....#..#
+..##..###
+#####.##.
+#####.##.
+..##..###
+#....#..#
+";
+
+    #[test]
+    fn reflection_scores_on_sample() {
+        let grids = <Solver as crate::Solver>::parse_input(SAMPLE.to_string()).unwrap();
+        let total = |num_change| {
+            grids
+                .iter()
+                .map(|grid| grid.reflection_score(num_change).unwrap())
+                .sum::<usize>()
+        };
+        assert_eq!(total(0), 405);
+        assert_eq!(total(1), 400);
+    }
+}
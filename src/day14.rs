@@ -1,13 +1,7 @@
-use crate::common::{Direction, Position};
+use crate::common::{self, Direction, Position};
 use failure::Error;
 use itertools::iproduct;
-use std::{collections::HashMap, fmt::Display};
-
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-pub struct Size {
-    width: usize,
-    height: usize,
-}
+use std::fmt::Display;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 enum GridEntry {
@@ -28,49 +22,39 @@ impl Display for GridEntry {
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Grid {
-    entries: Vec<GridEntry>,
-    size: Size,
+    entries: common::Grid<GridEntry>,
 }
 
 impl Grid {
-    fn get_index(&self, position: Position) -> Option<usize> {
-        if position.x >= 0
-            && position.x < self.size.width as i64
-            && position.y >= 0
-            && position.y < self.size.height as i64
-        {
-            Some((position.y * self.size.width as i64 + position.x) as usize)
-        } else {
-            None
-        }
+    fn get_entry(&self, position: Position) -> Option<GridEntry> {
+        self.entries.get(position).copied()
     }
 
-    fn get_entry(&self, position: Position) -> Option<GridEntry> {
-        self.get_index(position).map(|index| self.entries[index])
+    #[cfg(test)]
+    fn row(&self, y: usize) -> Vec<GridEntry> {
+        self.entries.row(y).to_vec()
     }
 
-    fn move_rock(&mut self, old_pos: Position, new_pos: Position) {
-        let old_index = self.get_index(old_pos).unwrap();
-        let new_index = self.get_index(new_pos).unwrap();
+    #[cfg(test)]
+    fn column(&self, x: usize) -> Vec<GridEntry> {
+        self.entries.column(x).cloned().collect()
+    }
 
-        self.entries[old_index] = GridEntry::Empty;
-        self.entries[new_index] = GridEntry::Movable;
+    fn move_rock(&mut self, old_pos: Position, new_pos: Position) {
+        *self.entries.get_mut(old_pos).unwrap() = GridEntry::Empty;
+        *self.entries.get_mut(new_pos).unwrap() = GridEntry::Movable;
     }
 
-    fn roll(&mut self, direction: Direction) {
+    pub fn roll(&mut self, direction: Direction) {
+        let width = self.entries.width();
+        let height = self.entries.height();
         let positions: Box<dyn Iterator<Item = Position>> = match direction {
-            Direction::North => {
-                Box::new(iproduct!(0..self.size.width, 0..self.size.height).map(Position::from))
-            }
-            Direction::East => Box::new(
-                iproduct!((0..self.size.width).rev(), 0..self.size.height).map(Position::from),
-            ),
-            Direction::South => Box::new(
-                iproduct!(0..self.size.width, (0..self.size.height).rev()).map(Position::from),
-            ),
-            Direction::West => {
-                Box::new(iproduct!(0..self.size.width, 0..self.size.height).map(Position::from))
+            Direction::North => Box::new(iproduct!(0..width, 0..height).map(Position::from)),
+            Direction::East => Box::new(iproduct!((0..width).rev(), 0..height).map(Position::from)),
+            Direction::South => {
+                Box::new(iproduct!(0..width, (0..height).rev()).map(Position::from))
             }
+            Direction::West => Box::new(iproduct!(0..width, 0..height).map(Position::from)),
         };
 
         for position in positions {
@@ -94,6 +78,19 @@ impl Grid {
         }
     }
 
+    // The number of movable rocks which can't roll any further in the given
+    // direction.
+    #[cfg(test)]
+    fn supported_rock_count(&self, direction: Direction) -> usize {
+        self.entries
+            .iter()
+            .filter(|(position, entry)| {
+                **entry == GridEntry::Movable
+                    && self.get_entry(position.step(direction)) != Some(GridEntry::Empty)
+            })
+            .count()
+    }
+
     fn cycle(&mut self) {
         self.roll(Direction::North);
         self.roll(Direction::West);
@@ -101,28 +98,20 @@ impl Grid {
         self.roll(Direction::East);
     }
 
-    fn total_load(&self) -> usize {
+    pub fn load(&self) -> usize {
         self.entries
             .iter()
-            .enumerate()
-            .map(|(index, entry)| {
-                if *entry == GridEntry::Movable {
-                    let row = index / self.size.width;
-                    self.size.height - row
-                } else {
-                    0
-                }
-            })
+            .filter(|(_, entry)| **entry == GridEntry::Movable)
+            .map(|(position, _)| self.entries.height() - position.y as usize)
             .sum()
     }
 }
 
 impl Display for Grid {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        for y in 0..self.size.height {
-            for x in 0..self.size.width {
-                let pos = (x, y).into();
-                write!(f, "{}", self.get_entry(pos).unwrap())?;
+        for y in 0..self.entries.height() {
+            for entry in self.entries.row(y) {
+                write!(f, "{}", entry)?;
             }
             writeln!(f)?;
         }
@@ -131,66 +120,132 @@ impl Display for Grid {
     }
 }
 
+fn after_cycles(grid: &Grid, cycles: usize) -> Grid {
+    common::find_cycle_and_project(
+        grid.clone(),
+        |grid| {
+            let mut grid = grid.clone();
+            grid.cycle();
+            grid
+        },
+        cycles,
+    )
+}
+
+// Total load on the north support beams after spinning the platform
+// `cycles` times.
+pub fn load_after_cycles(grid: &Grid, cycles: usize) -> usize {
+    after_cycles(grid, cycles).load()
+}
+
 pub struct Solver {}
 
 impl super::Solver for Solver {
     type Problem = Grid;
 
     fn parse_input(data: String) -> Result<Self::Problem, Error> {
-        let grid: Vec<Vec<_>> = data
-            .lines()
-            .map(|line| {
-                line.chars()
-                    .map(|c| match c {
-                        'O' => GridEntry::Movable,
-                        '#' => GridEntry::Static,
-                        _ => GridEntry::Empty,
-                    })
-                    .collect()
-            })
-            .collect();
-
-        let width = grid[0].len();
-        let height = grid.len();
+        let entries = common::Grid::from_char_grid(&data, |c| match c {
+            'O' => GridEntry::Movable,
+            '#' => GridEntry::Static,
+            _ => GridEntry::Empty,
+        })?;
 
-        let entries = grid.into_iter().flatten().collect();
-
-        Ok(Grid {
-            entries,
-            size: Size { width, height },
-        })
+        Ok(Grid { entries })
     }
 
     fn solve(grid: Self::Problem) -> (Option<String>, Option<String>) {
         let mut grid1 = grid.clone();
         grid1.roll(Direction::North);
-        let part1 = grid1.total_load();
+        let part1 = grid1.load();
 
-        let mut visited = HashMap::new();
-        let mut grid2 = grid.clone();
+        let part2 = load_after_cycles(&grid, 1_000_000_000);
 
-        let mut rem_spins = 1000000000;
-        while rem_spins > 0 {
-            if let Some(prev_spins) = visited.get(&grid2) {
-                let cycle_len = prev_spins - rem_spins;
-                rem_spins %= cycle_len;
+        (Some(part1.to_string()), Some(part2.to_string()))
+    }
+}
 
-                while rem_spins > 0 {
-                    grid2.cycle();
-                    rem_spins -= 1;
-                }
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = "O....#....
+O.OO#....#
+.....##...
+OO.#O....O
+.O.....O#.
+O.#..O.#.#
+..O..#O..O
+.......O..
+#....###..
+#OO..#....
+";
+
+    fn parse(data: &str) -> Grid {
+        <Solver as crate::Solver>::parse_input(data.to_string()).unwrap()
+    }
 
-                break;
-            } else {
-                visited.insert(grid2.clone(), rem_spins);
-            }
+    #[test]
+    fn column_spans_grid() {
+        let grid = parse(SAMPLE);
+        let column = grid.column(0);
+        assert_eq!(column.len(), 10);
+        assert_eq!(column[0], GridEntry::Movable);
+        assert_eq!(column[8], GridEntry::Static);
+        assert_eq!(grid.row(1)[4], GridEntry::Static);
+    }
 
-            grid2.cycle();
-            rem_spins -= 1;
+    #[test]
+    fn load_after_cycles_matches_manual_cycles() {
+        let grid = parse(SAMPLE);
+        let mut cycled = grid.clone();
+        for _ in 0..3 {
+            cycled.cycle();
         }
+        assert_eq!(load_after_cycles(&grid, 3), cycled.load());
+    }
 
-        let part2 = grid2.total_load();
+    #[test]
+    fn rolled_rocks_are_supported() {
+        let mut grid = parse(SAMPLE);
+        let num_movable = grid
+            .entries
+            .iter()
+            .filter(|(_, entry)| **entry == GridEntry::Movable)
+            .count();
+        assert!(grid.supported_rock_count(Direction::North) < num_movable);
+
+        for direction in [
+            Direction::North,
+            Direction::West,
+            Direction::South,
+            Direction::East,
+        ] {
+            grid.roll(direction);
+            assert_eq!(grid.supported_rock_count(direction), num_movable);
+        }
+    }
 
-        (Some(part1.to_string()), Some(part2.to_string()))
+    #[test]
+    fn spin_cycles_on_sample() {
+        let grid = parse(SAMPLE);
+        let expected = [
+            ".....#....\n....#...O#\n...OO##...\n.OO#......\n.....OOO#.\n\
+             .O#...O#.#\n....O#....\n......OOOO\n#...O###..\n#..OO#....\n",
+            ".....#....\n....#...O#\n.....##...\n..O#......\n.....OOO#.\n\
+             .O#...O#.#\n....O#...O\n.......OOO\n#..OO###..\n#.OOO#...O\n",
+            ".....#....\n....#...O#\n.....##...\n..O#......\n.....OOO#.\n\
+             .O#...O#.#\n....O#...O\n.......OOO\n#...O###.O\n#.OOO#...O\n",
+        ];
+        for (cycles, expected) in (1..).zip(expected) {
+            assert_eq!(after_cycles(&grid, cycles).to_string(), expected);
+        }
+        assert_eq!(load_after_cycles(&grid, 1_000_000_000), 64);
+    }
+
+    #[test]
+    fn roll_west_moves_rocks_fully_left() {
+        let mut grid = parse("..O.O\n#.O.O\nO..#O\n");
+        grid.roll(Direction::West);
+        assert_eq!(grid.to_string(), "OO...\n#OO..\nO..#O\n");
     }
 }
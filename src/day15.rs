@@ -86,7 +86,7 @@ impl FromStr for Instruction {
     }
 }
 
-fn hash(data: &str) -> u8 {
+pub fn hash(data: &str) -> u8 {
     let mut val: u8 = 0;
 
     for c in data.chars() {
@@ -131,6 +131,18 @@ fn display_lenses(lenses: &[Vec<Lens>; 256]) {
     }
 }
 
+fn solve_both(instructions: &[Instruction]) -> (u64, u64) {
+    let part1 = instructions
+        .iter()
+        .map(|instruction| instruction.hash as u64)
+        .sum();
+
+    let lenses = assemble_lenses(instructions);
+    let part2 = get_focussing_power(&lenses);
+
+    (part1, part2)
+}
+
 pub struct Solver {}
 
 impl super::Solver for Solver {
@@ -141,14 +153,28 @@ impl super::Solver for Solver {
     }
 
     fn solve(sequence: Self::Problem) -> (Option<String>, Option<String>) {
-        let part1 = sequence
-            .iter()
-            .map(|instruction| instruction.hash as u64)
-            .sum::<u64>();
-
-        let lenses = assemble_lenses(&sequence);
-        let part2 = get_focussing_power(&lenses);
+        let (part1, part2) = solve_both(&sequence);
 
         (Some(part1.to_string()), Some(part2.to_string()))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hash_of_steps() {
+        assert_eq!(hash("HASH"), 52);
+        assert_eq!(hash("rn=1"), 30);
+    }
+
+    #[test]
+    fn solve_both_on_sample() {
+        let instructions = <Solver as crate::Solver>::parse_input(
+            "rn=1,cm-,qp=3,cm=2,qp-,pc=4,ot=9,ab=5,pc-,pc=6,ot=7\n".to_string(),
+        )
+        .unwrap();
+        assert_eq!(solve_both(&instructions), (1320, 145));
+    }
+}
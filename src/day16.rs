@@ -1,7 +1,8 @@
 use crate::common::{Direction, Position};
 use failure::Error;
-use itertools::Either;
-use std::collections::{HashMap, HashSet};
+use itertools::{Either, Itertools};
+use rayon::prelude::*;
+use std::collections::{BTreeSet, HashMap, HashSet};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Mirror {
@@ -10,6 +11,22 @@ pub enum Mirror {
 }
 
 impl Mirror {
+    pub fn from_glyph(c: char) -> Option<Self> {
+        match c {
+            '/' => Some(Mirror::Right),
+            '\\' => Some(Mirror::Left),
+            _ => None,
+        }
+    }
+
+    #[cfg(test)]
+    pub fn to_glyph(self) -> char {
+        match self {
+            Mirror::Right => '/',
+            Mirror::Left => '\\',
+        }
+    }
+
     fn map_direction(self, dir: Direction) -> impl Iterator<Item = Direction> {
         use Direction::*;
         use Mirror::*;
@@ -34,6 +51,22 @@ pub enum Splitter {
 }
 
 impl Splitter {
+    pub fn from_glyph(c: char) -> Option<Self> {
+        match c {
+            '|' => Some(Splitter::Down),
+            '-' => Some(Splitter::Across),
+            _ => None,
+        }
+    }
+
+    #[cfg(test)]
+    pub fn to_glyph(self) -> char {
+        match self {
+            Splitter::Down => '|',
+            Splitter::Across => '-',
+        }
+    }
+
     fn map_direction(self, dir: Direction) -> impl Iterator<Item = Direction> {
         use Direction::*;
         use Splitter::*;
@@ -70,6 +103,14 @@ impl Objects {
     fn get(&self, pos: &Position) -> Option<&Object> {
         self.objects.get(pos)
     }
+
+    fn map_direction(&self, pos: Position, dir: Direction) -> impl Iterator<Item = Direction> {
+        if let Some(obj) = self.get(&pos) {
+            Either::Left(obj.map_direction(dir))
+        } else {
+            Either::Right([dir].into_iter())
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -79,6 +120,20 @@ pub enum Object {
 }
 
 impl Object {
+    fn from_glyph(c: char) -> Option<Self> {
+        Mirror::from_glyph(c)
+            .map(Object::Mirror)
+            .or_else(|| Splitter::from_glyph(c).map(Object::Splitter))
+    }
+
+    #[cfg(test)]
+    fn to_glyph(self) -> char {
+        match self {
+            Object::Mirror(mirror) => mirror.to_glyph(),
+            Object::Splitter(splitter) => splitter.to_glyph(),
+        }
+    }
+
     fn map_direction(self, dir: Direction) -> impl Iterator<Item = Direction> {
         match self {
             Object::Mirror(mirror) => Either::Left(mirror.map_direction(dir)),
@@ -87,8 +142,11 @@ impl Object {
     }
 }
 
-fn num_energised(objects: &Objects, start_pos: Position, start_dir: Direction) -> usize {
-    let mut energised = HashSet::new();
+fn beam_states(
+    objects: &Objects,
+    start_pos: Position,
+    start_dir: Direction,
+) -> HashSet<(Position, Direction)> {
     let mut visited = HashSet::new();
 
     let mut positions = vec![(start_pos, start_dir)];
@@ -97,17 +155,10 @@ fn num_energised(objects: &Objects, start_pos: Position, start_dir: Direction) -
         positions.retain(|loc| !visited.contains(loc));
         visited.extend(positions.clone());
 
-        energised.extend(positions.iter().map(|(pos, _)| *pos));
-
         positions = positions
             .into_iter()
             .flat_map(|(pos, dir)| {
-                if let Some(obj) = objects.get(&pos) {
-                    Either::Left(obj.map_direction(dir))
-                } else {
-                    Either::Right([dir].into_iter())
-                }
-                .filter_map(move |new_dir| {
+                objects.map_direction(pos, dir).filter_map(move |new_dir| {
                     let new_pos = pos.step(new_dir);
                     if !objects.pos_valid(new_pos) {
                         None
@@ -119,36 +170,225 @@ fn num_energised(objects: &Objects, start_pos: Position, start_dir: Direction) -
             .collect();
     }
 
-    energised.len()
+    visited
 }
 
-fn find_most_energised(objects: &Objects) -> usize {
-    use Direction::*;
-    Direction::all()
-        .flat_map(|dir| {
-            match dir {
-                North => Either::Left(Either::Left((0..=objects.max_x).map(|x| Position {
-                    x,
-                    y: objects.max_y,
-                }))),
-                East => Either::Left(Either::Right(
-                    (0..=objects.max_y).map(|y| Position { x: 0, y }),
-                )),
-                South => Either::Right(Either::Left(
-                    (0..=objects.max_x).map(|x| Position { x, y: 0 }),
-                )),
-                West => Either::Right(Either::Right((0..=objects.max_y).map(|y| Position {
-                    x: objects.max_x,
-                    y,
-                }))),
+type BeamState = (Position, Direction);
+
+// Follow a beam until it either leaves the grid, reaches a splitter which
+// splits it or goes round a loop, returning the states visited before that
+// and the splitting state.
+fn trace_segment(objects: &Objects, start: BeamState) -> (Vec<BeamState>, Option<BeamState>) {
+    let mut states = vec![];
+    let mut visited = HashSet::new();
+    let mut state = start;
+
+    loop {
+        let (pos, dir) = state;
+        let mut new_dirs = objects.map_direction(pos, dir);
+        let new_dir = new_dirs.next().unwrap();
+        if new_dirs.next().is_some() {
+            return (states, Some(state));
+        }
+
+        if !visited.insert(state) {
+            return (states, None);
+        }
+        states.push(state);
+
+        let new_pos = pos.step(new_dir);
+        if !objects.pos_valid(new_pos) {
+            return (states, None);
+        }
+        state = (new_pos, new_dir);
+    }
+}
+
+// The states reachable from every state in which a beam gets split, so that
+// beams from different starting points don't have to be traced past the first
+// splitter they reach.
+struct BeamCache {
+    splits: HashMap<BeamState, usize>,
+    reachable: Vec<HashSet<BeamState>>,
+    #[cfg(test)]
+    states_traced: usize,
+}
+
+impl BeamCache {
+    fn new(objects: &Objects) -> Self {
+        let nodes: Vec<BeamState> = objects
+            .objects
+            .keys()
+            .flat_map(|pos| Direction::all().map(move |dir| (*pos, dir)))
+            .filter(|&(pos, dir)| objects.map_direction(pos, dir).count() > 1)
+            .collect();
+        let node_index: HashMap<BeamState, usize> = nodes
+            .iter()
+            .enumerate()
+            .map(|(index, state)| (*state, index))
+            .collect();
+
+        let (segments, successors): (Vec<Vec<BeamState>>, Vec<Vec<usize>>) = nodes
+            .iter()
+            .map(|&(pos, dir)| {
+                let mut states = vec![(pos, dir)];
+                let mut next = vec![];
+                for new_dir in objects.map_direction(pos, dir) {
+                    let new_pos = pos.step(new_dir);
+                    if objects.pos_valid(new_pos) {
+                        let (segment, end) = trace_segment(objects, (new_pos, new_dir));
+                        states.extend(segment);
+                        next.extend(end.map(|state| node_index[&state]));
+                    }
+                }
+                (states, next)
+            })
+            .unzip();
+
+        #[cfg(test)]
+        let states_traced = segments.iter().map(Vec::len).sum();
+
+        // Splits within the same loop reach the same set of splits, so share
+        // the states between them.
+        let mut groups = HashMap::new();
+        let mut reachable = vec![];
+        let mut splits = HashMap::new();
+
+        for (index, state) in nodes.into_iter().enumerate() {
+            let mut reached = BTreeSet::from([index]);
+            let mut to_visit = vec![index];
+            while let Some(node) = to_visit.pop() {
+                for &next in &successors[node] {
+                    if reached.insert(next) {
+                        to_visit.push(next);
+                    }
+                }
             }
-            .map(move |pos| (pos, dir))
+
+            let group = *groups.entry(reached).or_insert_with_key(|reached| {
+                reachable.push(
+                    reached
+                        .iter()
+                        .flat_map(|&node| segments[node].iter().copied())
+                        .collect(),
+                );
+                reachable.len() - 1
+            });
+            splits.insert(state, group);
+        }
+
+        BeamCache {
+            splits,
+            reachable,
+            #[cfg(test)]
+            states_traced,
+        }
+    }
+
+    fn num_energised(&self, objects: &Objects, start_pos: Position, start_dir: Direction) -> usize {
+        let (segment, end) = trace_segment(objects, (start_pos, start_dir));
+        let reachable = end
+            .into_iter()
+            .flat_map(|state| self.reachable[self.splits[&state]].iter().copied());
+
+        segment
+            .into_iter()
+            .chain(reachable)
+            .map(|(pos, _)| pos)
+            .unique()
+            .count()
+    }
+}
+
+// The last positions within the grid visited by each beam, and the direction
+// in which the beam leaves the grid from there.
+#[cfg(test)]
+fn exit_points(
+    objects: &Objects,
+    start_pos: Position,
+    start_dir: Direction,
+) -> Vec<(Position, Direction)> {
+    beam_states(objects, start_pos, start_dir)
+        .into_iter()
+        .flat_map(|(pos, dir)| {
+            objects
+                .map_direction(pos, dir)
+                .filter(move |new_dir| !objects.pos_valid(pos.step(*new_dir)))
+                .map(move |new_dir| (pos, new_dir))
         })
-        .map(|(start_pos, start_dir)| num_energised(objects, start_pos, start_dir))
+        .unique()
+        .sorted_by_key(|(pos, dir)| (pos.y, pos.x, *dir as u8))
+        .collect()
+}
+
+fn num_energised(objects: &Objects, start_pos: Position, start_dir: Direction) -> usize {
+    beam_states(objects, start_pos, start_dir)
+        .into_iter()
+        .map(|(pos, _)| pos)
+        .unique()
+        .count()
+}
+
+// For each energised cell, the directions in which beams were travelling as
+// they entered it.
+#[cfg(test)]
+fn entry_directions(
+    objects: &Objects,
+    start_pos: Position,
+    start_dir: Direction,
+) -> HashMap<Position, HashSet<Direction>> {
+    let mut entries: HashMap<Position, HashSet<Direction>> = HashMap::new();
+
+    for (pos, dir) in beam_states(objects, start_pos, start_dir) {
+        entries.entry(pos).or_default().insert(dir);
+    }
+
+    entries
+}
+
+fn edge_starts(objects: &Objects) -> impl Iterator<Item = (Position, Direction)> + '_ {
+    use Direction::*;
+    Direction::all().flat_map(|dir| {
+        match dir {
+            North => Either::Left(Either::Left((0..=objects.max_x).map(|x| Position {
+                x,
+                y: objects.max_y,
+            }))),
+            East => Either::Left(Either::Right(
+                (0..=objects.max_y).map(|y| Position { x: 0, y }),
+            )),
+            South => Either::Right(Either::Left(
+                (0..=objects.max_x).map(|x| Position { x, y: 0 }),
+            )),
+            West => Either::Right(Either::Right((0..=objects.max_y).map(|y| Position {
+                x: objects.max_x,
+                y,
+            }))),
+        }
+        .map(move |pos| (pos, dir))
+    })
+}
+
+fn find_most_energised(objects: &Objects) -> usize {
+    let cache = BeamCache::new(objects);
+    edge_starts(objects)
+        .collect::<Vec<_>>()
+        .into_par_iter()
+        .map(|(start_pos, start_dir)| cache.num_energised(objects, start_pos, start_dir))
         .max()
         .unwrap()
 }
 
+#[cfg(test)]
+fn launch_extremes(objects: &Objects) -> (usize, usize) {
+    let cache = BeamCache::new(objects);
+    edge_starts(objects)
+        .map(|(start_pos, start_dir)| cache.num_energised(objects, start_pos, start_dir))
+        .minmax()
+        .into_option()
+        .unwrap()
+}
+
 pub struct Solver {}
 
 impl super::Solver for Solver {
@@ -160,14 +400,7 @@ impl super::Solver for Solver {
                 .enumerate()
                 .flat_map(|(y, line)| {
                     line.chars().enumerate().filter_map(move |(x, c)| {
-                        match c {
-                            '|' => Some(Object::Splitter(Splitter::Down)),
-                            '-' => Some(Object::Splitter(Splitter::Across)),
-                            '/' => Some(Object::Mirror(Mirror::Right)),
-                            '\\' => Some(Object::Mirror(Mirror::Left)),
-                            _ => None,
-                        }
-                        .map(|obj| ((x, y).into(), obj))
+                        Object::from_glyph(c).map(|obj| ((x, y).into(), obj))
                     })
                 })
                 .collect(),
@@ -180,3 +413,148 @@ impl super::Solver for Solver {
         (Some(part1.to_string()), Some(part2.to_string()))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = r".|...\....
+|.-.\.....
+.....|-...
+........|.
+..........
+.........\
+..../.\\..
+.-.-/..|..
+.|....-|.\
+..//.|....
+";
+
+    fn parse(data: &str) -> Objects {
+        <Solver as crate::Solver>::parse_input(data.to_string()).unwrap()
+    }
+
+    // A deterministic pseudo-random grid with a splitter in the bottom right
+    // corner so that it has the same size as the input.
+    fn random_grid(size: usize, mut seed: u64) -> Objects {
+        let mut data = String::new();
+        for y in 0..size {
+            for x in 0..size {
+                seed = seed
+                    .wrapping_mul(6364136223846793005)
+                    .wrapping_add(1442695040888963407);
+                data.push(if x == size - 1 && y == size - 1 {
+                    '|'
+                } else {
+                    match (seed >> 33) % 20 {
+                        0 => '|',
+                        1 => '-',
+                        2 => '/',
+                        3 => '\\',
+                        _ => '.',
+                    }
+                });
+            }
+            data.push('\n');
+        }
+        parse(&data)
+    }
+
+    #[test]
+    fn cache_matches_naive_search() {
+        for objects in [parse(SAMPLE), random_grid(40, 12345), random_grid(40, 999)] {
+            let cache = BeamCache::new(&objects);
+            for (pos, dir) in edge_starts(&objects) {
+                assert_eq!(
+                    cache.num_energised(&objects, pos, dir),
+                    num_energised(&objects, pos, dir)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn cache_expands_fewer_states() {
+        let objects = random_grid(40, 12345);
+        let cache = BeamCache::new(&objects);
+
+        let naive: usize = edge_starts(&objects)
+            .map(|(pos, dir)| beam_states(&objects, pos, dir).len())
+            .sum();
+        let cached: usize = cache.states_traced
+            + edge_starts(&objects)
+                .map(|start| trace_segment(&objects, start).0.len())
+                .sum::<usize>();
+
+        assert!(cached < naive, "{} >= {}", cached, naive);
+    }
+
+    #[test]
+    fn beam_loops_through_splitter_edge_on() {
+        let objects = parse("./\\\n...\n.|.\n.\\/\n");
+        let cache = BeamCache::new(&objects);
+        for (pos, dir) in edge_starts(&objects) {
+            assert_eq!(
+                cache.num_energised(&objects, pos, dir),
+                num_energised(&objects, pos, dir)
+            );
+        }
+
+        let (states, end) = trace_segment(&objects, (Position { x: 1, y: 1 }, Direction::North));
+        assert_eq!(end, None);
+        assert_eq!(states.len(), 8);
+    }
+
+    #[test]
+    fn launch_extremes_matches_separate_searches() {
+        let objects = parse(SAMPLE);
+        let darkest = edge_starts(&objects)
+            .map(|(pos, dir)| num_energised(&objects, pos, dir))
+            .min()
+            .unwrap();
+        assert_eq!(
+            launch_extremes(&objects),
+            (darkest, find_most_energised(&objects))
+        );
+    }
+
+    #[test]
+    fn entry_directions_reports_crossing_beams() {
+        // The beam is split downwards, goes round the bottom and comes back
+        // into the splitter from the other side.
+        let objects = parse(".|.\\\n....\n.\\./\n");
+        let entries = entry_directions(&objects, Position::origin(), Direction::East);
+        assert_eq!(
+            entries[&Position { x: 1, y: 0 }],
+            HashSet::from([Direction::East, Direction::West])
+        );
+    }
+
+    #[test]
+    fn straight_beam_exits_opposite_edge() {
+        let objects = parse("....\n....\n...-\n");
+        assert_eq!(
+            exit_points(&objects, Position { x: 0, y: 1 }, Direction::East),
+            vec![(Position { x: 3, y: 1 }, Direction::East)]
+        );
+    }
+
+    #[test]
+    fn most_energised_matches_serial_search() {
+        for objects in [parse(SAMPLE), random_grid(40, 12345)] {
+            let serial = edge_starts(&objects)
+                .map(|(pos, dir)| num_energised(&objects, pos, dir))
+                .max()
+                .unwrap();
+            assert_eq!(find_most_energised(&objects), serial);
+        }
+    }
+
+    #[test]
+    fn glyphs_round_trip() {
+        for glyph in ['/', '\\', '|', '-'] {
+            assert_eq!(Object::from_glyph(glyph).unwrap().to_glyph(), glyph);
+        }
+        assert_eq!(Object::from_glyph('.'), None);
+    }
+}
@@ -34,7 +34,7 @@ pub struct Grid {
 impl Grid {
     fn new(heat_loss: HashMap<Position, u64>) -> Self {
         let width = heat_loss.keys().map(|pos| pos.x).max().unwrap_or(0);
-        let height = heat_loss.keys().map(|pos| pos.x).max().unwrap_or(0);
+        let height = heat_loss.keys().map(|pos| pos.y).max().unwrap_or(0);
         Grid {
             width,
             height,
@@ -43,6 +43,16 @@ impl Grid {
     }
 }
 
+#[cfg(test)]
+fn cell_heat(grid: &Grid, pos: Position) -> Option<u64> {
+    grid.heat_loss.get(&pos).copied()
+}
+
+#[cfg(test)]
+fn total_grid_heat(grid: &Grid) -> u64 {
+    grid.heat_loss.values().sum()
+}
+
 impl FromStr for Grid {
     type Err = Error;
 
@@ -150,24 +160,52 @@ impl a_star::State for State<'_> {
     }
 }
 
-fn find_min_heat_loss(grid: &Grid, crucible: Crucible) -> u64 {
-    a_star::solve(
-        [Direction::East, Direction::South]
-            .into_iter()
-            .map(|direction| State {
-                grid,
-                crucible,
-                position: Position::origin(),
-                target: Position {
-                    x: grid.width,
-                    y: grid.height,
-                },
-                direction,
-                steps_in_direction: 0,
-            }),
-    )
-    .unwrap()
-    .cost
+fn start_states(grid: &Grid, crucible: Crucible) -> impl Iterator<Item = State<'_>> {
+    [Direction::East, Direction::South]
+        .into_iter()
+        .map(move |direction| State {
+            grid,
+            crucible,
+            position: Position::origin(),
+            target: Position {
+                x: grid.width,
+                y: grid.height,
+            },
+            direction,
+            steps_in_direction: 0,
+        })
+}
+
+fn find_min_heat_loss_solution(
+    grid: &Grid,
+    crucible: Crucible,
+) -> Option<a_star::Solution<State<'_>>> {
+    a_star::solve(start_states(grid, crucible)).ok()
+}
+
+fn find_min_heat_loss(grid: &Grid, crucible: Crucible) -> Option<u64> {
+    find_min_heat_loss_solution(grid, crucible).map(|solution| solution.cost)
+}
+
+// The minimum heat loss, if there's a route losing at most `max_heat_loss`.
+#[cfg(test)]
+fn find_min_heat_loss_within(grid: &Grid, crucible: Crucible, max_heat_loss: u64) -> Option<u64> {
+    a_star::solve_bounded(start_states(grid, crucible), max_heat_loss).map(|solution| solution.cost)
+}
+
+// The heat loss along with the positions visited from the start to the end.
+#[cfg(test)]
+fn min_heat_loss_path(grid: &Grid, crucible: Crucible) -> Option<(u64, Vec<Position>)> {
+    find_min_heat_loss_solution(grid, crucible).map(|solution| {
+        (
+            solution.cost,
+            solution
+                .route
+                .into_iter()
+                .map(|state| state.position)
+                .collect(),
+        )
+    })
 }
 
 pub struct Solver {}
@@ -192,6 +230,110 @@ impl super::Solver for Solver {
         };
         let part2 = find_min_heat_loss(&grid, ultra_crucible);
 
-        (Some(part1.to_string()), Some(part2.to_string()))
+        let display = |heat_loss: Option<u64>| {
+            heat_loss
+                .map(|heat_loss| heat_loss.to_string())
+                .unwrap_or_else(|| "infeasible".to_string())
+        };
+
+        (Some(display(part1)), Some(display(part2)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = "2413432311323
+3215453535623
+3255245654254
+3446585845452
+4546657867536
+1438598798454
+4457876987766
+3637877979653
+4654967986887
+4564679986453
+1224686865563
+2546548887735
+4322674655533
+";
+
+    const CRUCIBLE: Crucible = Crucible {
+        min_row: 0,
+        max_row: 3,
+    };
+
+    const ULTRA_CRUCIBLE: Crucible = Crucible {
+        min_row: 4,
+        max_row: 10,
+    };
+
+    #[test]
+    fn non_square_grid() {
+        // Every route which only moves right and down enters 12 + 6 cells.
+        let grid: Grid = "1111111111111\n".repeat(7).parse().unwrap();
+        assert_eq!((grid.width, grid.height), (12, 6));
+        assert_eq!(find_min_heat_loss(&grid, CRUCIBLE), Some(18));
+        assert_eq!(find_min_heat_loss(&grid, ULTRA_CRUCIBLE), Some(18));
+    }
+
+    #[test]
+    fn ultra_crucible_infeasible_on_tiny_grid() {
+        let grid: Grid = "12\n34\n".parse().unwrap();
+        assert_eq!(find_min_heat_loss(&grid, CRUCIBLE), Some(6));
+        assert_eq!(find_min_heat_loss(&grid, ULTRA_CRUCIBLE), None);
+        assert_eq!(
+            <Solver as crate::Solver>::solve(grid),
+            (Some("6".to_string()), Some("infeasible".to_string()))
+        );
+    }
+
+    #[test]
+    fn total_grid_heat_on_small_grid() {
+        let grid: Grid = "12\n34\n".parse().unwrap();
+        assert_eq!(total_grid_heat(&grid), 10);
+        assert_eq!(cell_heat(&grid, Position { x: 1, y: 0 }), Some(2));
+        assert_eq!(cell_heat(&grid, Position { x: 2, y: 0 }), None);
+    }
+
+    #[test]
+    fn min_heat_loss_path_is_contiguous() {
+        let grid: Grid = SAMPLE.parse().unwrap();
+        for (crucible, heat_loss) in [(CRUCIBLE, 102), (ULTRA_CRUCIBLE, 94)] {
+            let (cost, path) = min_heat_loss_path(&grid, crucible).unwrap();
+            assert_eq!(cost, heat_loss);
+            assert_eq!(path[0], Position::origin());
+            assert_eq!(*path.last().unwrap(), Position { x: 12, y: 12 });
+            assert!(path
+                .windows(2)
+                .all(|step| step[0].manhattan_distance_to(&step[1]) == 1));
+            assert_eq!(
+                path[1..].iter().map(|pos| grid.heat_loss[pos]).sum::<u64>(),
+                cost
+            );
+        }
+    }
+
+    #[test]
+    fn dijkstra_matches_a_star() {
+        let grid: Grid = SAMPLE.parse().unwrap();
+        for crucible in [CRUCIBLE, ULTRA_CRUCIBLE] {
+            assert_eq!(
+                a_star::dijkstra(start_states(&grid, crucible))
+                    .unwrap()
+                    .cost,
+                a_star::solve(start_states(&grid, crucible)).unwrap().cost
+            );
+        }
+    }
+
+    #[test]
+    fn bounded_search() {
+        let grid: Grid = SAMPLE.parse().unwrap();
+        assert_eq!(find_min_heat_loss_within(&grid, CRUCIBLE, 50), None);
+        assert_eq!(find_min_heat_loss_within(&grid, CRUCIBLE, 101), None);
+        assert_eq!(find_min_heat_loss_within(&grid, CRUCIBLE, 102), Some(102));
+        assert_eq!(find_min_heat_loss_within(&grid, CRUCIBLE, 1000), Some(102));
     }
 }
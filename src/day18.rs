@@ -3,7 +3,7 @@ use itertools::Itertools;
 use nom::{
     branch::alt,
     bytes::complete::{tag, take_while_m_n},
-    character::complete::{char, newline, space1},
+    character::complete::{char, newline, one_of, space1},
     combinator::{all_consuming, map, map_res, value},
     multi::many1,
     sequence::{delimited, separated_pair, terminated, tuple},
@@ -34,6 +34,19 @@ fn find_route(instructions: &[Instruction]) -> Vec<Position> {
         .collect()
 }
 
+#[cfg(test)]
+fn perimeter(instructions: &[Instruction]) -> i64 {
+    instructions
+        .iter()
+        .map(|instruction| instruction.length as i64)
+        .sum()
+}
+
+#[cfg(test)]
+fn route_is_closed(route: &[Position]) -> bool {
+    route.first() == route.last()
+}
+
 fn find_area(route: &[Position]) -> i64 {
     let ys: Vec<_> = route.iter().map(|pos| pos.y).unique().sorted().collect();
 
@@ -92,18 +105,39 @@ fn find_area(route: &[Position]) -> i64 {
         .sum()
 }
 
+// Alternative to `find_area` using the shoelace formula for the area
+// enclosed by the route and Pick's theorem to count the tiles within it.
+#[cfg(test)]
+fn area_shoelace(route: &[Position], perimeter: i64) -> i64 {
+    let area = route
+        .iter()
+        .tuple_windows()
+        .map(|(start, end)| start.x * end.y - end.x * start.y)
+        .sum::<i64>()
+        .abs()
+        / 2;
+
+    let interior = area - perimeter / 2 + 1;
+    interior + perimeter
+}
+
+fn solve_both(instructions: &[(Instruction, Instruction)]) -> (i64, i64) {
+    let (part1_instructions, part2_instructions): (Vec<_>, Vec<_>) =
+        instructions.iter().cloned().unzip();
+
+    (
+        find_area(&find_route(&part1_instructions)),
+        find_area(&find_route(&part2_instructions)),
+    )
+}
+
 pub struct Solver {}
 
 impl super::Solver for Solver {
     type Problem = Vec<(Instruction, Instruction)>;
 
     fn parse_input(data: String) -> Result<Self::Problem, Error> {
-        let letter_direction = alt((
-            value(Direction::North, char('U')),
-            value(Direction::East, char('R')),
-            value(Direction::South, char('D')),
-            value(Direction::West, char('L')),
-        ));
+        let letter_direction = map_res(one_of("URDL"), Direction::try_from);
 
         let part1_instruction = map(
             tuple((letter_direction, space1, unsigned)),
@@ -144,12 +178,60 @@ impl super::Solver for Solver {
     }
 
     fn solve(instructions: Self::Problem) -> (Option<String>, Option<String>) {
-        let (part1_instructions, part2_instructions): (Vec<_>, Vec<_>) =
-            instructions.iter().cloned().unzip();
-
-        let part1 = find_area(&find_route(&part1_instructions));
-        let part2 = find_area(&find_route(&part2_instructions));
+        let (part1, part2) = solve_both(&instructions);
 
         (Some(part1.to_string()), Some(part2.to_string()))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = "R 6 (#70c710)
+D 5 (#0dc571)
+L 2 (#5713f0)
+D 2 (#d2c081)
+R 2 (#59c680)
+D 2 (#411b91)
+L 5 (#8ceee2)
+U 2 (#caa173)
+L 1 (#1b58a2)
+U 2 (#caa171)
+R 2 (#7807d2)
+U 3 (#a77fa3)
+L 2 (#015232)
+U 2 (#7a21e3)
+";
+
+    fn parse() -> Vec<(Instruction, Instruction)> {
+        <Solver as crate::Solver>::parse_input(SAMPLE.to_string()).unwrap()
+    }
+
+    #[test]
+    fn solve_both_on_sample() {
+        assert_eq!(solve_both(&parse()), (62, 952408144115));
+    }
+
+    #[test]
+    fn shoelace_matches_scanline() {
+        let (part1, part2): (Vec<_>, Vec<_>) = parse().into_iter().unzip();
+        for instructions in [part1, part2] {
+            let route = find_route(&instructions);
+            assert_eq!(
+                area_shoelace(&route, perimeter(&instructions)),
+                find_area(&route)
+            );
+        }
+    }
+
+    #[test]
+    fn perimeter_of_closed_routes() {
+        let (part1, part2): (Vec<_>, Vec<_>) = parse().into_iter().unzip();
+        assert_eq!(perimeter(&part1), 38);
+        assert_eq!(perimeter(&part2), 6405262);
+        assert!(route_is_closed(&find_route(&part1)));
+        assert!(route_is_closed(&find_route(&part2)));
+        assert!(!route_is_closed(&find_route(&part1[..3])));
+    }
+}
@@ -3,7 +3,7 @@ mod parse {
     use nom::{
         branch::alt,
         bytes::complete::take_while1,
-        character::complete::{char, newline},
+        character::complete::{char, newline, satisfy},
         combinator::{all_consuming, map, opt, value},
         multi::{many1, separated_list1},
         sequence::{delimited, separated_pair, terminated, tuple},
@@ -23,12 +23,7 @@ mod parse {
     }
 
     fn category(input: &str) -> IResult<&str, Category> {
-        alt((
-            value(Category::Cool, char('x')),
-            value(Category::Musical, char('m')),
-            value(Category::Aerodynamic, char('a')),
-            value(Category::Shiny, char('s')),
-        ))(input)
+        satisfy(|c| c.is_ascii_lowercase())(input)
     }
 
     fn comparison(input: &str) -> IResult<&str, Comparison> {
@@ -105,6 +100,13 @@ mod parse {
         many1(terminated(part, newline))(input)
     }
 
+    #[cfg(test)]
+    pub(super) fn parse_part(input: &str) -> Result<Part, Error> {
+        all_consuming(part)(input)
+            .map(|(_, part)| part)
+            .map_err(|err| err_msg(format!("Failed to parse part: {}", err)))
+    }
+
     pub(super) fn parse_input(
         input: &str,
     ) -> Result<(HashMap<String, Workflow>, Vec<Part>), Error> {
@@ -114,10 +116,12 @@ mod parse {
     }
 }
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 
-use failure::Error;
+use failure::{err_msg, Error};
 use parse::parse_input;
+#[cfg(test)]
+use parse::parse_part;
 use std::{
     cmp::{max, min},
     ops::Range,
@@ -171,13 +175,7 @@ impl Comparison {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-enum Category {
-    Cool,
-    Musical,
-    Aerodynamic,
-    Shiny,
-}
+type Category = char;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 enum Outcome {
@@ -194,8 +192,10 @@ struct Condition {
 }
 
 impl Condition {
-    fn matches(&self, part: &Part) -> bool {
-        self.comparison.apply(part.value(self.category), self.value)
+    fn matches(&self, part: &Part) -> Result<bool, Error> {
+        Ok(self
+            .comparison
+            .apply(part.value(self.category)?, self.value))
     }
 
     fn split(&self, range: &PartRange) -> (Option<PartRange>, Option<PartRange>) {
@@ -215,16 +215,13 @@ struct Rule {
 }
 
 impl Rule {
-    fn get_outcome(&self, part: &Part) -> Option<&Outcome> {
-        if self
-            .condition
-            .map(|condition| condition.matches(part))
-            .unwrap_or(true)
-        {
-            Some(&self.outcome)
-        } else {
-            None
-        }
+    fn get_outcome(&self, part: &Part) -> Result<Option<&Outcome>, Error> {
+        let matches = match self.condition {
+            Some(condition) => condition.matches(part)?,
+            None => true,
+        };
+
+        Ok(if matches { Some(&self.outcome) } else { None })
     }
 
     fn split(&self, range: &PartRange) -> (Option<(PartRange, &Outcome)>, Option<PartRange>) {
@@ -247,8 +244,14 @@ pub struct Workflow {
 }
 
 impl Workflow {
-    fn outcome(&self, part: &Part) -> Option<&Outcome> {
-        self.rules.iter().find_map(|rule| rule.get_outcome(part))
+    fn outcome(&self, part: &Part) -> Result<Option<&Outcome>, Error> {
+        for rule in self.rules.iter() {
+            if let Some(outcome) = rule.get_outcome(part)? {
+                return Ok(Some(outcome));
+            }
+        }
+
+        Ok(None)
     }
 
     fn split(&self, range: PartRange) -> impl Iterator<Item = (PartRange, &Outcome)> + '_ {
@@ -268,61 +271,80 @@ impl Workflow {
     }
 }
 
-#[derive(Debug, Default)]
-pub struct Part {
-    cool: u64,
-    musical: u64,
-    aerodynamic: u64,
-    shiny: u64,
+fn get_workflow<'a>(
+    workflows: &'a HashMap<String, Workflow>,
+    name: &str,
+) -> Result<&'a Workflow, Error> {
+    workflows
+        .get(name)
+        .ok_or_else(|| err_msg(format!("Failed to find workflow: {}", name)))
 }
 
-impl Part {
-    fn value(&self, category: Category) -> u64 {
-        use Category::*;
-        match category {
-            Cool => self.cool,
-            Musical => self.musical,
-            Aerodynamic => self.aerodynamic,
-            Shiny => self.shiny,
+// Check every workflow which can be reached exists, and that every workflow
+// ends in a rule which always applies.
+fn validate_workflows(workflows: &HashMap<String, Workflow>) -> Result<(), Error> {
+    get_workflow(workflows, "in")?;
+
+    for workflow in workflows.values() {
+        for rule in workflow.rules.iter() {
+            if let Outcome::Jump(name) = &rule.outcome {
+                get_workflow(workflows, name)?;
+            }
         }
-    }
 
-    fn field_mut(&mut self, category: Category) -> &mut u64 {
-        use Category::*;
-        match category {
-            Cool => &mut self.cool,
-            Musical => &mut self.musical,
-            Aerodynamic => &mut self.aerodynamic,
-            Shiny => &mut self.shiny,
+        if workflow
+            .rules
+            .last()
+            .map(|rule| rule.condition.is_some())
+            .unwrap_or(true)
+        {
+            return Err(err_msg(format!(
+                "Workflow {} has no rule which always applies",
+                workflow.name
+            )));
         }
     }
 
+    Ok(())
+}
+
+#[derive(Debug, Default)]
+pub struct Part {
+    ratings: BTreeMap<Category, u64>,
+}
+
+impl Part {
+    fn value(&self, category: Category) -> Result<u64, Error> {
+        self.ratings
+            .get(&category)
+            .copied()
+            .ok_or_else(|| err_msg(format!("{:?} has no {} rating", self, category)))
+    }
+
     fn update(mut self, category: Category, value: u64) -> Self {
-        *self.field_mut(category) = value;
+        self.ratings.insert(category, value);
         self
     }
 
     fn total(&self) -> u64 {
-        self.cool + self.musical + self.aerodynamic + self.shiny
+        self.ratings.values().sum()
     }
 
-    fn is_accepted(&self, workflows: &HashMap<String, Workflow>) -> bool {
+    fn is_accepted(&self, workflows: &HashMap<String, Workflow>) -> Result<bool, Error> {
         let mut workflow_name = "in".to_string();
 
         loop {
-            let workflow = workflows
-                .get(&workflow_name)
-                .unwrap_or_else(|| panic!("Failed to find workflow: {}", workflow_name));
+            let workflow = get_workflow(workflows, &workflow_name)?;
 
-            match workflow.outcome(self) {
-                Some(Outcome::Accept) => break true,
-                Some(Outcome::Reject) => break false,
+            match workflow.outcome(self)? {
+                Some(Outcome::Accept) => break Ok(true),
+                Some(Outcome::Reject) => break Ok(false),
                 Some(Outcome::Jump(name)) => workflow_name = name.clone(),
                 None => {
-                    panic!(
-                        "No rule applied in workflow {:?} for part {:?}",
-                        workflow, self
-                    );
+                    break Err(err_msg(format!(
+                        "No rule applied in workflow {} for part {:?}",
+                        workflow.name, self
+                    )));
                 }
             }
         }
@@ -331,30 +353,25 @@ impl Part {
 
 #[derive(Debug, Clone)]
 pub struct PartRange {
-    cool: Range<u64>,
-    musical: Range<u64>,
-    aerodynamic: Range<u64>,
-    shiny: Range<u64>,
+    ranges: BTreeMap<Category, Range<u64>>,
 }
 
 impl PartRange {
-    fn full() -> Self {
+    fn full(categories: &BTreeSet<Category>) -> Self {
         PartRange {
-            cool: 1..4001,
-            musical: 1..4001,
-            aerodynamic: 1..4001,
-            shiny: 1..4001,
+            ranges: categories
+                .iter()
+                .map(|&category| (category, 1..4001))
+                .collect(),
         }
     }
 
-    fn split(self, workflows: &HashMap<String, Workflow>) -> Vec<(PartRange, bool)> {
+    fn split(self, workflows: &HashMap<String, Workflow>) -> Result<Vec<(PartRange, bool)>, Error> {
         let mut results = vec![];
         let mut to_split = vec![("in".to_string(), self)];
 
         while let Some((workflow_name, part_range)) = to_split.pop() {
-            let workflow = workflows
-                .get(&workflow_name)
-                .unwrap_or_else(|| panic!("Failed to find workflow: {}", workflow_name));
+            let workflow = get_workflow(workflows, &workflow_name)?;
 
             for (range, outcome) in workflow.split(part_range) {
                 match outcome {
@@ -365,64 +382,238 @@ impl PartRange {
             }
         }
 
-        results
+        Ok(results)
     }
 
     fn category_range(&self, category: Category) -> &Range<u64> {
-        use Category::*;
-        match category {
-            Cool => &self.cool,
-            Musical => &self.musical,
-            Aerodynamic => &self.aerodynamic,
-            Shiny => &self.shiny,
-        }
-    }
-
-    fn category_range_mut(&mut self, category: Category) -> &mut Range<u64> {
-        use Category::*;
-        match category {
-            Cool => &mut self.cool,
-            Musical => &mut self.musical,
-            Aerodynamic => &mut self.aerodynamic,
-            Shiny => &mut self.shiny,
-        }
+        &self.ranges[&category]
     }
 
     fn update(&self, category: Category, range: Range<u64>) -> Self {
         let mut updated = self.clone();
-        *updated.category_range_mut(category) = range;
+        updated.ranges.insert(category, range);
         updated
     }
 
+    #[cfg(test)]
+    fn start(&self) -> Part {
+        Part {
+            ratings: self
+                .ranges
+                .iter()
+                .map(|(&category, range)| (category, range.start))
+                .collect(),
+        }
+    }
+
     fn size(&self) -> u64 {
-        [&self.cool, &self.musical, &self.aerodynamic, &self.shiny]
-            .into_iter()
+        self.ranges
+            .values()
             .map(|range| range.end - range.start)
             .product()
     }
 }
 
+// The puzzle's x, m, a and s categories, along with any others checked by the
+// workflows.
+fn categories(workflows: &HashMap<String, Workflow>) -> BTreeSet<Category> {
+    let checked = workflows
+        .values()
+        .flat_map(|workflow| workflow.rules.iter())
+        .filter_map(|rule| rule.condition.map(|condition| condition.category));
+
+    "xmas".chars().chain(checked).collect()
+}
+
+// The ranges of parts accepted by the workflows, or `None` if they're invalid.
+#[cfg(test)]
+fn accepted_ranges(workflows: &HashMap<String, Workflow>) -> Option<Vec<PartRange>> {
+    let ranges = PartRange::full(&categories(workflows))
+        .split(workflows)
+        .ok()?
+        .into_iter()
+        .filter_map(|(range, accepted)| if accepted { Some(range) } else { None })
+        .collect();
+    Some(ranges)
+}
+
+// Any accepted part, taken from the start of the first accepted range.
+#[cfg(test)]
+fn minimal_accepting_part(workflows: &HashMap<String, Workflow>) -> Option<Part> {
+    accepted_ranges(workflows)?.first().map(PartRange::start)
+}
+
+// The accepted part with the lowest total rating.
+#[cfg(test)]
+fn smallest_accepted(workflows: &HashMap<String, Workflow>) -> Option<Part> {
+    accepted_ranges(workflows)?
+        .iter()
+        .map(PartRange::start)
+        .min_by_key(Part::total)
+}
+
+#[cfg(test)]
+fn classify_from_str(workflows: &HashMap<String, Workflow>, part_str: &str) -> Result<bool, Error> {
+    let part = parse_part(part_str.trim())?;
+    part.is_accepted(workflows)
+}
+
+fn solve_both(workflows: &HashMap<String, Workflow>, parts: &[Part]) -> Result<(u64, u64), Error> {
+    let mut part1 = 0;
+    for part in parts {
+        if part.is_accepted(workflows)? {
+            part1 += part.total();
+        }
+    }
+
+    let part2 = PartRange::full(&categories(workflows))
+        .split(workflows)?
+        .into_iter()
+        .filter_map(|(range, accepted)| if accepted { Some(range.size()) } else { None })
+        .sum();
+
+    Ok((part1, part2))
+}
+
 pub struct Solver {}
 
 impl super::Solver for Solver {
     type Problem = (HashMap<String, Workflow>, Vec<Part>);
 
     fn parse_input(data: String) -> Result<Self::Problem, Error> {
-        parse_input(&data)
+        let (workflows, parts) = parse_input(&data)?;
+        validate_workflows(&workflows)?;
+        Ok((workflows, parts))
     }
 
     fn solve((workflows, parts): Self::Problem) -> (Option<String>, Option<String>) {
-        let part1: u64 = parts
-            .iter()
-            .filter(|part| part.is_accepted(&workflows))
-            .map(|part| part.total())
-            .sum();
-
-        let part2: u64 = PartRange::full()
-            .split(&workflows)
-            .into_iter()
-            .filter_map(|(range, accepted)| if accepted { Some(range.size()) } else { None })
-            .sum();
-        (Some(part1.to_string()), Some(part2.to_string()))
+        // Workflows are validated when parsed, but parts may still be missing
+        // the ratings they check.
+        match solve_both(&workflows, &parts) {
+            Ok((part1, part2)) => (Some(part1.to_string()), Some(part2.to_string())),
+            Err(err) => (Some(err.to_string()), Some(err.to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = "px{a<2006:qkq,m>2090:A,rfg}
+pv{a>1716:R,A}
+lnx{m>1548:A,A}
+rfg{s<537:gd,x>2440:R,A}
+qs{s>3448:A,lnx}
+qkq{x<1416:A,crn}
+crn{x>2662:A,R}
+in{s<1351:px,qqz}
+qqz{s>2770:qs,m<1801:hdj,R}
+gd{a>3333:R,R}
+hdj{m>838:A,pv}
+
+{x=787,m=2655,a=1222,s=2876}
+{x=1679,m=44,a=2067,s=496}
+{x=2036,m=264,a=79,s=2244}
+{x=2461,m=1339,a=466,s=291}
+{x=2127,m=1623,a=2188,s=1013}
+";
+
+    fn parse(data: &str) -> (HashMap<String, Workflow>, Vec<Part>) {
+        <Solver as crate::Solver>::parse_input(data.to_string()).unwrap()
+    }
+
+    #[test]
+    fn minimal_accepting_part_is_accepted() {
+        let (workflows, _) = parse(SAMPLE);
+        let part = minimal_accepting_part(&workflows).unwrap();
+        assert!(part.is_accepted(&workflows).unwrap());
+        assert_eq!(part.ratings.keys().collect::<String>(), "amsx");
+
+        let (workflows, _) = parse("in{x<100:R,A}\n\n{x=1}\n");
+        let part = minimal_accepting_part(&workflows).unwrap();
+        for (category, rating) in [('x', 100), ('m', 1), ('a', 1), ('s', 1)] {
+            assert_eq!(part.value(category).unwrap(), rating);
+        }
+
+        let (workflows, _) = parse("in{R}\n\n{x=1}\n");
+        assert!(minimal_accepting_part(&workflows).is_none());
+    }
+
+    #[test]
+    fn smallest_accepted_is_accepted() {
+        let (workflows, _) = parse(SAMPLE);
+        let part = smallest_accepted(&workflows).unwrap();
+        assert!(part.is_accepted(&workflows).unwrap());
+        assert_eq!(part.total(), 4);
+
+        let (workflows, _) = parse("in{x<100:R,s<50:R,A}\n\n{x=1}\n");
+        assert_eq!(
+            smallest_accepted(&workflows).unwrap().total(),
+            100 + 1 + 1 + 50
+        );
+        assert_eq!(
+            smallest_accepted(&workflows).unwrap().value('m').unwrap(),
+            1
+        );
+
+        let (workflows, _) = parse("in{R}\n\n{x=1}\n");
+        assert!(smallest_accepted(&workflows).is_none());
+    }
+
+    #[test]
+    fn classify_sample_parts() {
+        let (workflows, _) = parse(SAMPLE);
+        assert!(classify_from_str(&workflows, "{x=787,m=2655,a=1222,s=2876}").unwrap());
+        assert!(!classify_from_str(&workflows, "{x=1679,m=44,a=2067,s=496}\n").unwrap());
+        assert!(classify_from_str(&workflows, "{x=1679").is_err());
+    }
+
+    #[test]
+    fn solve_both_on_sample() {
+        let (workflows, parts) = parse(SAMPLE);
+        assert_eq!(
+            solve_both(&workflows, &parts).unwrap(),
+            (19114, 167409079868000)
+        );
+    }
+
+    #[test]
+    fn undefined_workflow_is_an_error() {
+        let data = "in{x<10:foo,A}\n\n{x=1,m=1,a=1,s=1}\n";
+        assert!(<Solver as crate::Solver>::parse_input(data.to_string()).is_err());
+
+        let (workflows, parts) = parse_input(data).unwrap();
+        assert!(parts[0].is_accepted(&workflows).is_err());
+        assert!(solve_both(&workflows, &parts).is_err());
+    }
+
+    #[test]
+    fn custom_category() {
+        let (workflows, parts) = parse("in{z>10:A,R}\n\n{z=11,y=3}\n{z=2,y=3}\n");
+        assert_eq!(
+            solve_both(&workflows, &parts).unwrap(),
+            (14, 3990 * 4000_u64.pow(4))
+        );
+
+        let (workflows, parts) = parse("in{z>10:A,R}\n\n{z=11,y=3,w=1}\n");
+        assert_eq!(
+            solve_both(&workflows, &parts).unwrap(),
+            (15, 3990 * 4000_u64.pow(4))
+        );
+    }
+
+    #[test]
+    fn missing_rating_is_an_error() {
+        let (workflows, parts) = parse("in{m>10:A,R}\n\n{x=1}\n");
+        assert!(parts[0].value('m').is_err());
+        assert!(parts[0].is_accepted(&workflows).is_err());
+        assert!(solve_both(&workflows, &parts).is_err());
+        assert_eq!(
+            <Solver as crate::Solver>::solve((workflows, parts))
+                .0
+                .unwrap(),
+            "Part { ratings: {'x': 1} } has no m rating"
+        );
     }
 }
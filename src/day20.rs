@@ -9,8 +9,9 @@ use nom::{
     sequence::{separated_pair, terminated, tuple},
     IResult,
 };
+use num::integer::lcm;
 use std::{
-    collections::{HashMap, VecDeque},
+    collections::{HashMap, HashSet, VecDeque},
     fmt::Display,
 };
 
@@ -195,17 +196,48 @@ fn modules(input: &str) -> IResult<&str, Vec<Module>> {
     many1(terminated(module, newline))(input)
 }
 
+#[cfg(test)]
+fn get_flipflop(modules: &HashMap<String, Module>, name: &str) -> Result<bool, Error> {
+    match modules.get(name).map(|module| &module.handler) {
+        Some(ModuleHandler::FlipFlop(flipflop)) => Ok(flipflop.on),
+        Some(_) => Err(err_msg(format!("Module {} is not a flip-flop", name))),
+        None => Err(err_msg(format!("Unknown module {}", name))),
+    }
+}
+
+#[cfg(test)]
+fn set_flipflop(modules: &mut HashMap<String, Module>, name: &str, on: bool) -> Result<(), Error> {
+    match modules.get_mut(name).map(|module| &mut module.handler) {
+        Some(ModuleHandler::FlipFlop(flipflop)) => {
+            flipflop.on = on;
+            Ok(())
+        }
+        Some(_) => Err(err_msg(format!("Module {} is not a flip-flop", name))),
+        None => Err(err_msg(format!("Unknown module {}", name))),
+    }
+}
+
 fn press_button(modules: &mut HashMap<String, Module>) -> (usize, usize) {
     let mut num_low = 0;
     let mut num_high = 0;
+
+    press_button_with(modules, |pulse, _, _| match pulse {
+        Pulse::Low => num_low += 1,
+        Pulse::High => num_high += 1,
+    });
+
+    (num_low, num_high)
+}
+
+fn press_button_with<F>(modules: &mut HashMap<String, Module>, mut on_pulse: F)
+where
+    F: FnMut(Pulse, &str, &str),
+{
     let mut pulses = VecDeque::new();
     pulses.push_back((Pulse::Low, "broadcaster".to_string(), "button".to_string()));
 
     while let Some((pulse, destination, source)) = pulses.pop_front() {
-        match pulse {
-            Pulse::Low => num_low += 1,
-            Pulse::High => num_high += 1,
-        }
+        on_pulse(pulse, &source, &destination);
 
         if let Some(module) = modules.get_mut(&destination) {
             if let Some(new_pulse) = module.handler.handle_pulse(pulse, &source) {
@@ -215,8 +247,6 @@ fn press_button(modules: &mut HashMap<String, Module>) -> (usize, usize) {
             }
         }
     }
-
-    (num_low, num_high)
 }
 
 fn count_pulses(mut modules: HashMap<String, Module>, num_presses: usize) -> (usize, usize) {
@@ -227,64 +257,159 @@ fn count_pulses(mut modules: HashMap<String, Module>, num_presses: usize) -> (us
         })
 }
 
-fn display_header(modules: &HashMap<String, Module>) {
-    let names = modules.keys().sorted().collect::<Vec<_>>();
-    print!("presses");
+fn network_state(modules: &HashMap<String, Module>) -> Vec<bool> {
+    modules
+        .keys()
+        .sorted()
+        .flat_map(|name| match &modules[name].handler {
+            ModuleHandler::FlipFlop(flipflop) => vec![flipflop.on],
+            ModuleHandler::Conjunction(conjunction) => conjunction
+                .last_pulse
+                .keys()
+                .sorted()
+                .map(|source| conjunction.last_pulse[source] == Pulse::High)
+                .collect(),
+            ModuleHandler::Broadcast(_) => vec![],
+        })
+        .collect()
+}
 
-    for name in names.iter() {
-        let module = modules.get(*name).unwrap();
+// Searches which keep pressing the button give up after this many presses.
+const MAX_PRESSES: u64 = 1_000_000;
 
-        if let ModuleHandler::Conjunction(conjunction) = &module.handler {
-            for _ in conjunction.last_pulse.keys() {
-                print!(",{}", name)
-            }
-        } else {
-            print!(",{}", name);
+#[cfg(test)]
+fn pulse_period(modules: &HashMap<String, Module>) -> Option<usize> {
+    let mut modules = modules.clone();
+    let initial = network_state(&modules);
+    let mut visited = HashSet::new();
+
+    for presses in 1..=MAX_PRESSES as usize {
+        press_button(&mut modules);
+        let state = network_state(&modules);
+
+        if state == initial {
+            return Some(presses);
+        }
+
+        if !visited.insert(state) {
+            return None;
         }
     }
-    println!();
 
-    for name in names {
-        let module = modules.get(name).unwrap();
+    None
+}
 
-        if let ModuleHandler::Conjunction(conjunction) = &module.handler {
-            let sources = conjunction.last_pulse.keys().sorted();
-            for source in sources {
-                print!(",{}", source)
+// The target is fed by a single conjunction, which only sends a low pulse once
+// all of its inputs have most recently sent it a high pulse.  Each input sends
+// high pulses on its own regular cycle, so the first press at which they all
+// line up is the LCM of the presses at which each first sends a high pulse.
+//
+// Once the network repeats a state the pulses it sends repeat too, so any input
+// which hasn't sent a high pulse by then never will.
+fn presses_until_low(modules: &HashMap<String, Module>, target: &str) -> Option<u64> {
+    let feeder = modules
+        .values()
+        .find(|module| module.output.iter().any(|dest| dest == target))?;
+
+    let ModuleHandler::Conjunction(conjunction) = &feeder.handler else {
+        return None;
+    };
+
+    let feeder_name = feeder.name.clone();
+    let num_inputs = conjunction.last_pulse.len();
+    let mut first_high: HashMap<String, u64> = HashMap::new();
+    let mut modules = modules.clone();
+    let mut visited = HashSet::from([network_state(&modules)]);
+
+    for presses in 1..=MAX_PRESSES {
+        press_button_with(&mut modules, |pulse, source, destination| {
+            if pulse == Pulse::High && destination == feeder_name {
+                first_high.entry(source.to_string()).or_insert(presses);
             }
-        } else {
-            print!(",");
+        });
+
+        if first_high.len() == num_inputs {
+            return Some(first_high.into_values().fold(1, lcm));
+        }
+
+        if !visited.insert(network_state(&modules)) {
+            return None;
         }
     }
 
-    println!();
+    None
 }
 
-fn display_modules(presses: usize, modules: &HashMap<String, Module>) {
-    print!("{}", presses);
+#[cfg(test)]
+fn reachable_from(modules: &HashMap<String, Module>, start: &str) -> HashSet<String> {
+    let mut reachable = HashSet::from([start.to_string()]);
+    let mut to_visit = vec![start.to_string()];
+
+    while let Some(name) = to_visit.pop() {
+        if let Some(module) = modules.get(&name) {
+            for dest in module.output.iter() {
+                if dest != "broadcaster" && reachable.insert(dest.clone()) {
+                    to_visit.push(dest.clone());
+                }
+            }
+        }
+    }
 
-    let names = modules.keys().sorted();
+    reachable
+}
 
-    for name in names {
-        let module = modules.get(name).unwrap();
+// Split the network into the parts fed by each output of the broadcaster,
+// excluding any modules where those parts reconverge.
+#[cfg(test)]
+fn subgraphs(modules: &HashMap<String, Module>) -> Vec<HashSet<String>> {
+    let Some(broadcaster) = modules.get("broadcaster") else {
+        return vec![];
+    };
+
+    let reachable: Vec<_> = broadcaster
+        .output
+        .iter()
+        .map(|start| reachable_from(modules, start))
+        .collect();
+
+    let shared: HashSet<_> = reachable.iter().flatten().duplicates().cloned().collect();
+
+    reachable
+        .into_iter()
+        .map(|names| &names - &shared)
+        .collect()
+}
 
-        match &module.handler {
-            ModuleHandler::Conjunction(conjunction) => {
-                let sources = conjunction.last_pulse.keys().sorted();
+#[cfg(test)]
+fn to_dot(modules: &HashMap<String, Module>) -> String {
+    let mut dot = String::from("digraph modules {\n");
+
+    for name in modules.keys().sorted() {
+        let (label, shape) = match &modules[name].handler {
+            ModuleHandler::FlipFlop(_) => (format!("%{}", name), "box"),
+            ModuleHandler::Conjunction(_) => (format!("&{}", name), "diamond"),
+            ModuleHandler::Broadcast(_) => (name.clone(), "doublecircle"),
+        };
+        dot.push_str(&format!(
+            "    {} [label=\"{}\", shape={}];\n",
+            name, label, shape
+        ));
+    }
 
-                for source in sources {
-                    print!(",{}", conjunction.last_pulse.get(source).unwrap());
-                }
-            }
-            ModuleHandler::FlipFlop(flipflop) => {
-                print!(",{}", flipflop);
-            }
-            ModuleHandler::Broadcast(_) => {
-                print!(",1");
-            }
+    for name in modules.keys().sorted() {
+        for dest in modules[name].output.iter() {
+            dot.push_str(&format!("    {} -> {};\n", name, dest));
         }
     }
-    println!();
+
+    dot.push_str("}\n");
+    dot
+}
+
+// Part 2 is only defined for networks which feed an `rx` module.
+fn solve_both(modules: &HashMap<String, Module>) -> (usize, Option<u64>) {
+    let (low, high) = count_pulses(modules.clone(), 1000);
+    (low * high, presses_until_low(modules, "rx"))
 }
 
 pub struct Solver {}
@@ -319,17 +444,131 @@ impl super::Solver for Solver {
         Ok(modules)
     }
 
-    fn solve(mut modules: Self::Problem) -> (Option<String>, Option<String>) {
-        let (low, high) = count_pulses(modules.clone(), 1000);
-        let part1 = low * high;
+    fn solve(modules: Self::Problem) -> (Option<String>, Option<String>) {
+        let (part1, part2) = solve_both(&modules);
 
-        display_header(&modules);
-        display_modules(0, &modules);
-        for index in 1..=64000 {
-            press_button(&mut modules);
-            display_modules(index, &modules);
+        (
+            Some(part1.to_string()),
+            part2.map(|presses| presses.to_string()),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = "broadcaster -> a, b, c
+%a -> b
+%b -> c
+%c -> inv
+&inv -> a
+";
+
+    const SAMPLE2: &str = "broadcaster -> a
+%a -> inv, con
+&inv -> b
+%b -> con
+&con -> output
+";
+
+    // Two binary counters feeding a conjunction in front of `rx`.
+    const COUNTERS: &str = "broadcaster -> a, c
+%a -> b
+%b -> x
+&x -> hub
+%c -> d
+%d -> e
+%e -> y
+&y -> hub
+&hub -> rx
+";
+
+    fn parse(data: &str) -> HashMap<String, Module> {
+        <Solver as crate::Solver>::parse_input(data.to_string()).unwrap()
+    }
+
+    #[test]
+    fn flipflop_chain_period() {
+        let modules = parse("broadcaster -> a\n%a -> b\n%b -> c\n%c -> out\n");
+        assert_eq!(pulse_period(&modules), Some(8));
+    }
+
+    #[test]
+    fn forced_flipflop_turns_off() {
+        let mut modules = parse("broadcaster -> a\n%a -> b\n&b -> out\n");
+        assert!(!get_flipflop(&modules, "a").unwrap());
+        set_flipflop(&mut modules, "a", true).unwrap();
+        assert!(get_flipflop(&modules, "a").unwrap());
+        assert!(set_flipflop(&mut modules, "b", true).is_err());
+        assert!(set_flipflop(&mut modules, "zz", true).is_err());
+
+        let mut pulses = vec![];
+        press_button_with(&mut modules, |pulse, source, destination| {
+            pulses.push((pulse, source.to_string(), destination.to_string()))
+        });
+        assert!(pulses.contains(&(Pulse::Low, "a".to_string(), "b".to_string())));
+        assert!(!get_flipflop(&modules, "a").unwrap());
+    }
+
+    #[test]
+    fn dot_has_every_edge() {
+        let modules = parse(SAMPLE2);
+        let dot = to_dot(&modules);
+        assert!(dot.contains("broadcaster [label=\"broadcaster\", shape=doublecircle];"));
+        assert_eq!(
+            dot.matches(" -> ").count(),
+            modules
+                .values()
+                .map(|module| module.output.len())
+                .sum::<usize>()
+        );
+    }
+
+    #[test]
+    fn one_subgraph_per_broadcast_output() {
+        let subgraphs = subgraphs(&parse(COUNTERS));
+        assert_eq!(
+            subgraphs,
+            vec![
+                HashSet::from(["a", "b", "x"].map(str::to_string)),
+                HashSet::from(["c", "d", "e", "y"].map(str::to_string)),
+            ]
+        );
+    }
+
+    #[test]
+    fn solve_both_on_samples() {
+        assert_eq!(solve_both(&parse(SAMPLE)), (32000000, None));
+        assert_eq!(solve_both(&parse(SAMPLE2)).0, 11687500);
+    }
+
+    #[test]
+    fn presses_until_low_matches_simulation() {
+        let mut modules = parse(COUNTERS);
+        let mut presses = 0;
+        let mut rx_low = false;
+        while !rx_low {
+            presses += 1;
+            press_button_with(&mut modules, |pulse, _, destination| {
+                rx_low |= pulse == Pulse::Low && destination == "rx";
+            });
         }
 
-        (Some(part1.to_string()), None)
+        assert_eq!(presses_until_low(&parse(COUNTERS), "rx"), Some(presses));
+    }
+
+    #[test]
+    fn presses_until_low_never_low() {
+        // Nothing sends `b` a pulse, so `con` never sees a high pulse from it.
+        let modules = parse("broadcaster -> a\n%a -> con\n%b -> con\n&con -> rx\n");
+        assert_eq!(presses_until_low(&modules, "rx"), None);
+    }
+
+    #[test]
+    fn pulse_period_never_returns() {
+        // `c` remembers a high pulse from `inv` forever after the first press.
+        let modules = parse("broadcaster -> inv\n&inv -> c\n&c -> out\n");
+        assert_eq!(pulse_period(&modules), None);
     }
 }
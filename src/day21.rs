@@ -1,8 +1,11 @@
-use crate::common::Position;
-use failure::Error;
-use itertools::Itertools;
+use crate::{common::Position, parsers::char_grid};
+use failure::{err_msg, Error};
+use itertools::{iterate, Itertools};
 use std::collections::HashSet;
 
+const PART1_STEPS: u64 = 64;
+const PART2_STEPS: u64 = 26501365;
+
 pub struct Grid {
     rocks: HashSet<Position>,
     max_x: i64,
@@ -15,24 +18,98 @@ impl Grid {
         position.x >= 0 && position.x <= self.max_x && position.y >= 0 && position.y <= self.max_y
     }
 
-    fn can_move_to(&self, position: Position) -> bool {
-        self.is_valid(position) && !self.rocks.contains(&position)
+    fn wrap(&self, position: Position) -> Position {
+        position.wrap(self.max_x + 1, self.max_y + 1)
     }
-}
 
-fn find_max_plots(grid: &Grid, num_steps: u64) -> usize {
-    let mut current = vec![grid.start];
+    fn can_move_to(&self, position: Position, infinite: bool) -> bool {
+        if infinite {
+            !self.rocks.contains(&self.wrap(position))
+        } else {
+            self.is_valid(position) && !self.rocks.contains(&position)
+        }
+    }
+}
 
-    for _ in 0..num_steps {
-        current = current
-            .into_iter()
+fn plot_counts(grid: &Grid, infinite: bool) -> impl Iterator<Item = usize> + '_ {
+    iterate(vec![grid.start], move |current| {
+        current
+            .iter()
             .flat_map(|pos| pos.adjacent())
             .unique()
-            .filter(|pos| grid.can_move_to(*pos))
-            .collect();
+            .filter(|pos| grid.can_move_to(*pos, infinite))
+            .collect()
+    })
+    .map(|current| current.len())
+}
+
+// Count the plots reachable in exactly `num_steps` steps by direct simulation.
+pub fn reachable_plots(grid: &Grid, num_steps: u64, infinite: bool) -> usize {
+    plot_counts(grid, infinite).nth(num_steps as usize).unwrap()
+}
+
+fn find_max_plots(grid: &Grid, num_steps: u64) -> usize {
+    reachable_plots(grid, num_steps, false)
+}
+
+// On the infinite grid the number of reachable plots after `offset + n * period`
+// steps grows quadratically in `n` (given the clear row and column through the
+// start of the real input), so sample three points and extrapolate.
+fn find_max_plots_infinite(grid: &Grid, num_steps: u64) -> Result<usize, Error> {
+    let period = (grid.max_x + 1) as u64;
+    let offset = num_steps % period;
+
+    if num_steps <= offset + 2 * period {
+        return Ok(reachable_plots(grid, num_steps, true));
+    }
+
+    // The differences between samples can be negative, so fit the quadratic
+    // using signed values.
+    let samples: Vec<_> = plot_counts(grid, true)
+        .skip(offset as usize)
+        .step_by(period as usize)
+        .take(3)
+        .map(|count| count as i128)
+        .collect();
+    let (y0, y1, y2) = (samples[0], samples[1], samples[2]);
+
+    let n = (num_steps / period) as i128;
+    let plots = y0 + n * (y1 - y0) + n * (n - 1) / 2 * (y2 + y0 - 2 * y1);
+
+    usize::try_from(plots).map_err(|_| {
+        err_msg(format!(
+            "Extrapolated an invalid number of plots: {}",
+            plots
+        ))
+    })
+}
+
+#[cfg(test)]
+fn unreachable_open_plots(grid: &Grid) -> usize {
+    let mut reachable = HashSet::from([grid.start]);
+    let mut to_visit = vec![grid.start];
+
+    while let Some(pos) = to_visit.pop() {
+        for next_pos in pos.adjacent() {
+            if grid.can_move_to(next_pos, false) && reachable.insert(next_pos) {
+                to_visit.push(next_pos);
+            }
+        }
     }
 
-    current.len()
+    let num_open = ((grid.max_x + 1) * (grid.max_y + 1)) as usize - grid.rocks.len();
+    num_open - reachable.len()
+}
+
+fn solve_with_steps(
+    grid: &Grid,
+    part1_steps: u64,
+    part2_steps: u64,
+) -> Result<(usize, usize), Error> {
+    Ok((
+        find_max_plots(grid, part1_steps),
+        find_max_plots_infinite(grid, part2_steps)?,
+    ))
 }
 
 pub struct Solver {}
@@ -41,32 +118,22 @@ impl super::Solver for Solver {
     type Problem = Grid;
 
     fn parse_input(data: String) -> Result<Self::Problem, Error> {
-        let rocks = data
-            .lines()
-            .enumerate()
-            .flat_map(|(y, line)| {
-                line.chars().enumerate().filter_map(move |(x, c)| {
-                    if c == '#' {
-                        Some((x, y).into())
-                    } else {
-                        None
-                    }
-                })
-            })
+        let tiles = char_grid(&data, |c| matches!(c, '#' | 'S').then_some(c));
+
+        let rocks = tiles
+            .iter()
+            .filter(|(_, &c)| c == '#')
+            .map(|(position, _)| *position)
             .collect();
 
         let max_x = (data.lines().next().unwrap().len() - 1) as i64;
         let max_y = (data.lines().count() - 1) as i64;
 
-        let start = data
-            .lines()
-            .enumerate()
-            .find_map(|(y, line)| {
-                line.chars()
-                    .enumerate()
-                    .find_map(|(x, c)| if c == 'S' { Some((x, y).into()) } else { None })
-            })
-            .unwrap();
+        let start = tiles
+            .iter()
+            .find(|(_, &c)| c == 'S')
+            .map(|(position, _)| *position)
+            .ok_or(err_msg("Failed to find start position"))?;
 
         Ok(Grid {
             rocks,
@@ -77,8 +144,85 @@ impl super::Solver for Solver {
     }
 
     fn solve(grid: Self::Problem) -> (Option<String>, Option<String>) {
-        let part1 = find_max_plots(&grid, 64);
+        match solve_with_steps(&grid, PART1_STEPS, PART2_STEPS) {
+            Ok((part1, part2)) => (Some(part1.to_string()), Some(part2.to_string())),
+            Err(err) => (Some(err.to_string()), Some(err.to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = "...........
+.....###.#.
+.###.##..#.
+..#.#...#..
+....#.#....
+.##..S####.
+.##..#...#.
+.......##..
+.##.#.####.
+.##..##.##.
+...........
+";
+
+    fn parse(data: &str) -> Grid {
+        <Solver as crate::Solver>::parse_input(data.to_string()).unwrap()
+    }
+
+    #[test]
+    fn reachable_plots_on_sample() {
+        let grid = parse(SAMPLE);
+        assert_eq!(reachable_plots(&grid, 6, false), 16);
+        assert_eq!(reachable_plots(&grid, 6, true), 16);
+        assert_eq!(reachable_plots(&grid, 10, true), 50);
+        assert_eq!(reachable_plots(&grid, 50, true), 1594);
+        assert_eq!(reachable_plots(&grid, 100, true), 6536);
+    }
+
+    #[test]
+    fn infinite_extrapolation_matches_simulation() {
+        let grid = parse(".....\n.....\n..S..\n.....\n.....\n");
+        for steps in [17, 23, 41, 59] {
+            assert_eq!(
+                find_max_plots_infinite(&grid, steps).unwrap(),
+                reachable_plots(&grid, steps, true)
+            );
+        }
+    }
+
+    #[test]
+    fn solve_with_steps_on_sample_and_open_grid() {
+        assert_eq!(solve_with_steps(&parse(SAMPLE), 6, 10).unwrap(), (16, 50));
+
+        // On an open grid every plot of the right parity within the diamond is reachable.
+        let open = parse(".....\n.....\n..S..\n.....\n.....\n");
+        assert_eq!(solve_with_steps(&open, 2, 1002).unwrap(), (9, 1003 * 1003));
+    }
+
+    #[test]
+    fn infinite_counts_on_sample() {
+        let grid = parse(SAMPLE);
+        assert_eq!(find_max_plots_infinite(&grid, 6).unwrap(), 16);
+        assert_eq!(find_max_plots_infinite(&grid, 10).unwrap(), 50);
+    }
+
+    #[test]
+    fn unreachable_open_plots_in_walled_off_pocket() {
+        let grid = parse("S.....\n.###..\n.#..#.\n.###..\n......\n");
+        assert_eq!(unreachable_open_plots(&grid), 2);
+
+        assert_eq!(unreachable_open_plots(&parse("S..\n.#.\n...\n")), 0);
+    }
 
-        (Some(part1.to_string()), None)
+    #[test]
+    fn infinite_extrapolation_with_shrinking_counts() {
+        // The start is boxed in with one plot either side, so the count
+        // alternates between 1 and 2 and the fitted quadratic goes negative.
+        let grid = parse("#####\n#.S.#\n#####\n");
+        assert_eq!(find_max_plots_infinite(&grid, 10).unwrap(), 1);
+        assert!(find_max_plots_infinite(&grid, 100).is_err());
     }
 }
@@ -1,8 +1,12 @@
 use aocf::Aoc;
-use failure::Error;
+use failure::{err_msg, Error};
+use serde_json::json;
+use std::collections::HashMap;
 use std::fs::read_to_string;
-use std::path::Path;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
+use std::time::{Duration, Instant};
 
 mod a_star;
 mod common;
@@ -29,12 +33,25 @@ mod day20;
 mod day21;
 mod parsers;
 
+pub use day15::hash;
+
+pub const NUM_DAYS: u32 = 21;
+
 #[derive(Debug, Eq, PartialEq)]
 pub enum Part {
     One,
     Two,
 }
 
+impl Part {
+    fn number(&self) -> u8 {
+        match self {
+            Part::One => 1,
+            Part::Two => 2,
+        }
+    }
+}
+
 impl FromStr for Part {
     type Err = String;
 
@@ -58,11 +75,65 @@ fn read_from_server(aoc: &mut Aoc) -> Result<String, Error> {
     aoc.get_input(false)
 }
 
+// Where `fetch` saves the input for a day unless told otherwise.
+pub fn input_path(day: u32) -> PathBuf {
+    PathBuf::from(format!("day{:02}.input", day))
+}
+
+// Read the input which `fetch` saved for a day.
+pub fn read_saved_input(day: u32) -> Result<String, Error> {
+    let path = input_path(day);
+    read_to_string(&path).map_err(|err| match err.kind() {
+        io::ErrorKind::NotFound => err_msg(format!(
+            "{} not found, use `fetch {}` to download it",
+            path.display(),
+            day
+        )),
+        _ => err_msg(format!("failed to read {}: {}", path.display(), err)),
+    })
+}
+
+pub fn fetch_input(day: u32, session: &str) -> Result<String, Error> {
+    let url = format!("https://adventofcode.com/2023/day/{}/input", day);
+
+    match ureq::get(&url)
+        .set("Cookie", &format!("session={}", session))
+        .call()
+    {
+        Ok(response) => Ok(response.into_string()?),
+        Err(ureq::Error::Status(400, _)) => Err(err_msg(
+            "Server rejected the request, check AOC_SESSION is a valid session token",
+        )),
+        Err(ureq::Error::Status(404, _)) => Err(err_msg(format!(
+            "Input for day {} isn't available yet",
+            day
+        ))),
+        Err(ureq::Error::Status(code, response)) => Err(err_msg(format!(
+            "Unexpected response fetching input: {} {}",
+            code,
+            response.status_text()
+        ))),
+        Err(err) => Err(err_msg(format!("Failed to fetch input: {}", err))),
+    }
+}
+
+fn read_from_stdin() -> Result<String, Error> {
+    let mut data = String::new();
+    io::stdin().read_to_string(&mut data)?;
+
+    if data.trim().is_empty() {
+        return Err(err_msg("No input provided on stdin"));
+    }
+
+    Ok(data)
+}
+
+// A path of `-` reads the input from stdin.
 pub fn read_input<P: AsRef<Path>>(path: Option<P>, aoc: &mut Aoc) -> Result<String, Error> {
-    if let Some(path) = &path {
-        Ok(read_to_string(path)?)
-    } else {
-        read_from_server(aoc)
+    match &path {
+        Some(path) if path.as_ref() == Path::new("-") => read_from_stdin(),
+        Some(path) => Ok(read_to_string(path)?),
+        None => read_from_server(aoc),
     }
 }
 
@@ -74,54 +145,234 @@ fn display_solution(part: usize, solution: &str) {
     }
 }
 
-pub fn solve<S: Solver>(data: String, aoc: &mut Aoc, submit: Option<Part>) -> Result<(), Error> {
+pub struct Solution {
+    pub part_one: Option<String>,
+    pub part_two: Option<String>,
+    pub parse_time: Duration,
+    pub solve_time: Duration,
+}
+
+impl Solution {
+    pub fn to_json(&self, day: u32) -> serde_json::Value {
+        json!({
+            "day": day,
+            "part1": self.part_one,
+            "part2": self.part_two,
+            "parse_us": self.parse_time.as_micros() as u64,
+            "solve_us": self.solve_time.as_micros() as u64,
+        })
+    }
+}
+
+// A parsed problem ready to be solved, so the two phases can be run (and
+// timed) separately.
+pub struct Prepared {
+    solve: Box<dyn FnOnce() -> (Option<String>, Option<String>)>,
+    parse_time: Duration,
+}
+
+impl Prepared {
+    pub fn solve(self) -> Solution {
+        let start = Instant::now();
+        let (part_one, part_two) = (self.solve)();
+        let solve_time = start.elapsed();
+
+        Solution {
+            part_one,
+            part_two,
+            parse_time: self.parse_time,
+            solve_time,
+        }
+    }
+}
+
+fn prepare<S: Solver + 'static>(data: String) -> Result<Prepared, Error>
+where
+    S::Problem: 'static,
+{
+    let start = Instant::now();
     let problem = S::parse_input(data)?;
-    let (part_one, part_two) = S::solve(problem);
+    let parse_time = start.elapsed();
+
+    Ok(Prepared {
+        solve: Box::new(move || S::solve(problem)),
+        parse_time,
+    })
+}
 
-    if let Some(solution) = part_one {
-        display_solution(1, &solution);
+pub fn prepare_day(day: u32, data: String) -> Result<Prepared, Error> {
+    match day {
+        1 => prepare::<day01::Solver>(data),
+        2 => prepare::<day02::Solver>(data),
+        3 => prepare::<day03::Solver>(data),
+        4 => prepare::<day04::Solver>(data),
+        5 => prepare::<day05::Solver>(data),
+        6 => prepare::<day06::Solver>(data),
+        7 => prepare::<day07::Solver>(data),
+        8 => prepare::<day08::Solver>(data),
+        9 => prepare::<day09::Solver>(data),
+        10 => prepare::<day10::Solver>(data),
+        11 => prepare::<day11::Solver>(data),
+        12 => prepare::<day12::Solver>(data),
+        13 => prepare::<day13::Solver>(data),
+        14 => prepare::<day14::Solver>(data),
+        15 => prepare::<day15::Solver>(data),
+        16 => prepare::<day16::Solver>(data),
+        17 => prepare::<day17::Solver>(data),
+        18 => prepare::<day18::Solver>(data),
+        19 => prepare::<day19::Solver>(data),
+        20 => prepare::<day20::Solver>(data),
+        21 => prepare::<day21::Solver>(data),
+        _ => Err(failure::err_msg(format!("Invalid day {}", day))),
+    }
+}
 
-        if submit == Some(Part::One) {
-            let outcome = (*aoc).submit(&solution)?;
-            println!("{}", outcome);
+pub fn run_day(day: u32, data: String) -> Result<Solution, Error> {
+    Ok(prepare_day(day, data)?.solve())
+}
+
+// Known answers keyed by day and part.
+pub type Answers = HashMap<(u32, u8), String>;
+
+// Each line of the answers file is of the form `<day> <part> <answer>`.
+pub fn parse_answers(data: &str) -> Result<Answers, Error> {
+    data.lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            let mut fields = line.splitn(3, ' ');
+            let (Some(day), Some(part), Some(answer)) =
+                (fields.next(), fields.next(), fields.next())
+            else {
+                return Err(err_msg(format!("Invalid answer line: {}", line)));
+            };
+            Ok(((day.parse()?, part.parse()?), answer.to_string()))
+        })
+        .collect()
+}
+
+fn check_answer(answers: &Answers, day: u32, part: u8, answer: Option<&str>) -> String {
+    match (answers.get(&(day, part)), answer) {
+        (None, _) => "unverified".to_string(),
+        (Some(expected), Some(answer)) if expected == answer => "OK".to_string(),
+        (Some(expected), answer) => format!(
+            "MISMATCH (got {}, want {})",
+            answer.unwrap_or("-"),
+            expected
+        ),
+    }
+}
+
+pub fn solve_day(
+    day: u32,
+    data: String,
+    aoc: &mut Aoc,
+    submit: Option<Part>,
+    answers: Option<&Answers>,
+) -> Result<(), Error> {
+    let solution = run_day(day, data)?;
+
+    for (part, answer) in [
+        (Part::One, &solution.part_one),
+        (Part::Two, &solution.part_two),
+    ] {
+        let part_num = part.number();
+
+        if let Some(answer) = answer {
+            display_solution(part_num as usize, answer);
+
+            if submit == Some(part) {
+                let outcome = aoc.submit(answer)?;
+                println!("{}", outcome);
+            }
         }
+
+        if let Some(answers) = answers {
+            println!(
+                "Part {} check: {}",
+                part_num,
+                check_answer(answers, day, part_num, answer.as_deref())
+            );
+        }
+    }
+
+    println!(
+        "parse: {:.1?}, solve: {:.1?}",
+        solution.parse_time, solution.solve_time
+    );
+
+    Ok(())
+}
+
+// Run every day, reporting any which can't be run rather than stopping.
+pub fn solve_all_days(
+    mut read_input: impl FnMut(u32) -> Result<String, Error>,
+    answers: Option<&Answers>,
+    as_json: bool,
+) -> Result<(), Error> {
+    let mut rows = vec![];
+
+    for day in 1..=NUM_DAYS {
+        let solution = read_input(day)
+            .map_err(|err| format!("failed to read input: {}", err))
+            .and_then(|data| run_day(day, data).map_err(|err| format!("failed to solve: {}", err)));
+
+        match solution {
+            Ok(solution) => rows.push((day, solution)),
+            Err(err) => eprintln!("Warning: day {}: {}", day, err),
+        }
+    }
+
+    if as_json {
+        let results: serde_json::Value = rows
+            .iter()
+            .map(|(day, solution)| solution.to_json(*day))
+            .collect();
+        println!("{}", results);
+        return Ok(());
     }
 
-    if let Some(solution) = part_two {
-        display_solution(2, &solution);
+    print!(
+        "{:>3} | {:>20} | {:>20} | {:>10}",
+        "day", "part1", "part2", "elapsed"
+    );
+    if answers.is_some() {
+        print!(" | check1 | check2");
+    }
+    println!();
 
-        if submit == Some(Part::Two) {
-            let outcome = aoc.submit(&solution)?;
-            println!("{}", outcome);
+    for (day, solution) in rows {
+        let display = |answer: &Option<String>| answer.clone().unwrap_or_else(|| "-".to_string());
+        print!(
+            "{:>3} | {:>20} | {:>20} | {:>10}",
+            day,
+            display(&solution.part_one),
+            display(&solution.part_two),
+            format!("{:.1?}", solution.parse_time + solution.solve_time)
+        );
+        if let Some(answers) = answers {
+            print!(
+                " | {} | {}",
+                check_answer(answers, day, 1, solution.part_one.as_deref()),
+                check_answer(answers, day, 2, solution.part_two.as_deref())
+            );
         }
+        println!();
     }
 
     Ok(())
 }
 
-pub fn solve_day(day: u32, data: String, aoc: &mut Aoc, submit: Option<Part>) -> Result<(), Error> {
-    match day {
-        1 => solve::<day01::Solver>(data, aoc, submit),
-        2 => solve::<day02::Solver>(data, aoc, submit),
-        3 => solve::<day03::Solver>(data, aoc, submit),
-        4 => solve::<day04::Solver>(data, aoc, submit),
-        5 => solve::<day05::Solver>(data, aoc, submit),
-        6 => solve::<day06::Solver>(data, aoc, submit),
-        7 => solve::<day07::Solver>(data, aoc, submit),
-        8 => solve::<day08::Solver>(data, aoc, submit),
-        9 => solve::<day09::Solver>(data, aoc, submit),
-        10 => solve::<day10::Solver>(data, aoc, submit),
-        11 => solve::<day11::Solver>(data, aoc, submit),
-        12 => solve::<day12::Solver>(data, aoc, submit),
-        13 => solve::<day13::Solver>(data, aoc, submit),
-        14 => solve::<day14::Solver>(data, aoc, submit),
-        15 => solve::<day15::Solver>(data, aoc, submit),
-        16 => solve::<day16::Solver>(data, aoc, submit),
-        17 => solve::<day17::Solver>(data, aoc, submit),
-        18 => solve::<day18::Solver>(data, aoc, submit),
-        19 => solve::<day19::Solver>(data, aoc, submit),
-        20 => solve::<day20::Solver>(data, aoc, submit),
-        21 => solve::<day21::Solver>(data, aoc, submit),
-        _ => Err(failure::err_msg(format!("Invalid day {}", day))),
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn json_round_trips() {
+        let data = "32T3K 765\nT55J5 684\nKK677 28\nKTJJT 220\nQQQJA 483\n";
+        let json = run_day(7, data.to_string()).unwrap().to_json(7).to_string();
+        let parsed: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed["day"], 7);
+        assert_eq!(parsed["part1"], "6440");
+        assert_eq!(parsed["part2"], "5905");
     }
 }
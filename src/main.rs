@@ -1,33 +1,129 @@
 use aocf::Aoc;
 use failure::Error;
 
+use std::env;
 use std::path::PathBuf;
-use structopt::StructOpt;
+use structopt::{clap::AppSettings, StructOpt};
 
-use aoc2023::{read_input, solve_day, Part};
+use aoc2023::{
+    fetch_input, input_path, parse_answers, read_input, read_saved_input, run_day, solve_all_days,
+    solve_day, Part,
+};
+use std::fs::{read_to_string, write};
 
 #[derive(StructOpt, Debug)]
+#[structopt(setting = AppSettings::SubcommandsNegateReqs)]
 struct Opt {
-    day: u32,
+    #[structopt(subcommand)]
+    command: Option<Command>,
+
+    #[structopt(required_unless = "all")]
+    day: Option<u32>,
+
+    /// Where to read the input from, defaults to dayNN.input if it has been fetched
     input: Option<PathBuf>,
 
     #[structopt(long)]
     submit: Option<Part>,
+
+    /// Read the input from stdin, equivalent to an input of `-`
+    #[structopt(long, conflicts_with = "input")]
+    stdin: bool,
+
+    /// Run every day and print a summary table
+    #[structopt(long, conflicts_with_all = &["day", "submit", "stdin"])]
+    all: bool,
+
+    /// File of known answers to check the solutions against
+    #[structopt(long)]
+    answers: Option<PathBuf>,
+
+    /// Print the results as JSON
+    #[structopt(long, conflicts_with_all = &["submit", "answers"])]
+    json: bool,
 }
 
-fn main() -> Result<(), Error> {
-    let opt = Opt::from_args();
+#[derive(StructOpt, Debug)]
+enum Command {
+    /// Download the input for a day using the session token in AOC_SESSION
+    Fetch {
+        day: u32,
+
+        /// Where to save the input, defaults to dayNN.input
+        output: Option<PathBuf>,
+
+        /// Overwrite the input if it has already been downloaded
+        #[structopt(long)]
+        force: bool,
+    },
+}
+
+fn fetch(day: u32, output: Option<PathBuf>, force: bool) -> Result<(), Error> {
+    let path = output.unwrap_or_else(|| input_path(day));
+    if path.exists() && !force {
+        return Err(failure::err_msg(format!(
+            "{} already exists, use --force to overwrite it",
+            path.display()
+        )));
+    }
 
-    let mut aoc = Aoc::new()
+    let session = env::var("AOC_SESSION")
+        .map_err(|_| failure::err_msg("AOC_SESSION must be set to fetch input"))?;
+
+    let data = fetch_input(day, &session)?;
+    write(&path, data)?;
+    println!("Saved input for day {} to {}", day, path.display());
+
+    Ok(())
+}
+
+fn init_aoc(day: u32) -> Result<Aoc, Error> {
+    Aoc::new()
         .parse_cli(false)
         .year(Some(2023))
-        .day(Some(opt.day))
-        .init()?;
+        .day(Some(day))
+        .init()
+}
+
+fn main() -> Result<(), Error> {
+    let opt = Opt::from_args();
+
+    if let Some(Command::Fetch { day, output, force }) = opt.command {
+        return fetch(day, output, force);
+    }
+
+    let answers = opt
+        .answers
+        .map(|path| {
+            read_to_string(path)
+                .map_err(Error::from)
+                .and_then(|data| parse_answers(&data))
+                .map_err(|err| failure::err_msg(format!("Failed to read answers: {}", err)))
+        })
+        .transpose()?;
+
+    if opt.all {
+        return solve_all_days(read_saved_input, answers.as_ref(), opt.json);
+    }
+
+    let day = opt.day.unwrap();
+    let mut aoc = init_aoc(day)?;
+
+    let input = if opt.stdin {
+        Some(PathBuf::from("-"))
+    } else {
+        opt.input
+            .or_else(|| Some(input_path(day)).filter(|path| path.exists()))
+    };
 
-    let data = read_input(opt.input, &mut aoc)
+    let data = read_input(input, &mut aoc)
         .map_err(|err| failure::err_msg(format!("Failed to read input: {}", err)))?;
 
-    solve_day(opt.day, data, &mut aoc, opt.submit)?;
+    if opt.json {
+        println!("{}", run_day(day, data)?.to_json(day));
+    } else {
+        solve_day(day, data, &mut aoc, opt.submit, answers.as_ref())?;
+    }
 
     Ok(())
 }
@@ -1,12 +1,14 @@
 #![allow(unused)]
+use crate::common::Position;
 use nom::{
     bytes::complete::{tag, take_while1},
-    character::complete::digit1,
+    character::complete::{digit1, space0, space1},
     combinator::{map_res, opt, recognize},
-    sequence::pair,
+    multi::separated_list1,
+    sequence::{delimited, pair},
     IResult,
 };
-use std::str::FromStr;
+use std::{collections::HashMap, str::FromStr};
 
 pub fn unsigned<T: FromStr>(input: &str) -> IResult<&str, T> {
     map_res(take_while1(|c: char| c.is_ascii_digit()), |size: &str| {
@@ -14,8 +16,64 @@ pub fn unsigned<T: FromStr>(input: &str) -> IResult<&str, T> {
     })(input)
 }
 
-pub fn signed(input: &str) -> IResult<&str, i64> {
+pub fn signed<T: FromStr>(input: &str) -> IResult<&str, T> {
     map_res(recognize(pair(opt(tag("-")), digit1)), |val: &str| {
         val.parse()
     })(input)
 }
+
+// A list of integers separated by spaces, ignoring any leading or trailing spaces.
+pub fn int_list<T: FromStr>(input: &str) -> IResult<&str, Vec<T>> {
+    delimited(space0, separated_list1(space1, unsigned), space0)(input)
+}
+
+// The items in a grid of characters keyed by their position, skipping any
+// characters which don't map to an item.
+pub fn char_grid<T>(data: &str, mut f: impl FnMut(char) -> Option<T>) -> HashMap<Position, T> {
+    data.lines()
+        .enumerate()
+        .flat_map(|(y, line)| line.chars().enumerate().map(move |(x, c)| ((x, y), c)))
+        .filter_map(|(position, c)| f(c).map(|item| (position.into(), item)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signed_values() {
+        assert_eq!(signed::<i64>("-42 x"), Ok((" x", -42)));
+        assert_eq!(signed::<i32>("0"), Ok(("", 0)));
+        assert_eq!(signed::<i8>("17,"), Ok((",", 17)));
+        assert!(signed::<u8>("-1").is_err());
+        assert!(signed::<i64>("x").is_err());
+    }
+
+    #[test]
+    fn int_list_spacing() {
+        assert_eq!(
+            int_list::<u64>(" 1 21 53 59 44 | 69"),
+            Ok(("| 69", vec![1, 21, 53, 59, 44]))
+        );
+        assert_eq!(
+            int_list::<u32>("  7  15   30\n"),
+            Ok(("\n", vec![7, 15, 30]))
+        );
+        assert_eq!(int_list::<u32>("7"), Ok(("", vec![7])));
+        assert!(int_list::<u32>("  x").is_err());
+    }
+
+    #[test]
+    fn char_grid_skips_unmapped() {
+        let grid = char_grid("a.b\n..c\n", |c| (c != '.').then_some(c));
+        assert_eq!(
+            grid,
+            HashMap::from([
+                (Position { x: 0, y: 0 }, 'a'),
+                (Position { x: 2, y: 0 }, 'b'),
+                (Position { x: 2, y: 1 }, 'c'),
+            ])
+        );
+    }
+}